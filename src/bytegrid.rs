@@ -8,6 +8,7 @@ use std::{
 
 extern crate test;
 
+#[derive(Clone)]
 pub struct ByteGrid {
     pub width: i32,
     pub height: i32,
@@ -119,6 +120,107 @@ pub const SOUTHWEST: Point = Point { row: 1, col: -1 };
 pub const SOUTH: Point = Point { row: 1, col: 0 };
 pub const SOUTHEAST: Point = Point { row: 1, col: 1 };
 
+/// A compass heading, in clockwise order starting from north
+#[derive(Debug, Hash, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Direction {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+    pub const CARDINALS: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    /// Turn clockwise by `eighths` eighths of a full turn (negative for
+    /// anticlockwise)
+    pub fn rotate(self, eighths: i32) -> Direction {
+        Direction::ALL[(self as i32 + eighths).rem_euclid(8) as usize]
+    }
+
+    pub fn turn_right(self) -> Direction {
+        self.rotate(2)
+    }
+
+    pub fn turn_left(self) -> Direction {
+        self.rotate(-2)
+    }
+
+    pub fn turn_around(self) -> Direction {
+        self.rotate(4)
+    }
+
+    pub fn is_cardinal(self) -> bool {
+        (self as u8).is_multiple_of(2)
+    }
+
+    pub fn to_point(self) -> Point {
+        match self {
+            Direction::North => NORTH,
+            Direction::NorthEast => NORTHEAST,
+            Direction::East => EAST,
+            Direction::SouthEast => SOUTHEAST,
+            Direction::South => SOUTH,
+            Direction::SouthWest => SOUTHWEST,
+            Direction::West => WEST,
+            Direction::NorthWest => NORTHWEST,
+        }
+    }
+
+    /// The direction of a unit step, e.g. `EAST`
+    pub fn from_point(p: Point) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|d| d.to_point() == p)
+    }
+
+    /// Parses arrows (`^>v<`), compass letters (`NESW`) and `UDLR`
+    pub fn from_byte(b: u8) -> Option<Direction> {
+        match b {
+            b'^' | b'N' | b'U' => Some(Direction::North),
+            b'>' | b'E' | b'R' => Some(Direction::East),
+            b'v' | b'S' | b'D' => Some(Direction::South),
+            b'<' | b'W' | b'L' => Some(Direction::West),
+            _ => None,
+        }
+    }
+
+    /// An arrow for cardinal directions, and a slash for diagonals
+    pub fn to_arrow(self) -> u8 {
+        match self {
+            Direction::North => b'^',
+            Direction::East => b'>',
+            Direction::South => b'v',
+            Direction::West => b'<',
+            Direction::NorthEast | Direction::SouthWest => b'/',
+            Direction::SouthEast | Direction::NorthWest => b'\\',
+        }
+    }
+}
+
+impl From<Direction> for Point {
+    fn from(d: Direction) -> Self {
+        d.to_point()
+    }
+}
+
 pub struct ByteGridIter<'a> {
     grid: &'a ByteGrid,
     pub row: i32,
@@ -256,11 +358,11 @@ impl ByteGrid {
         }
     }
 
-    pub fn rows(&self) -> Chunks<u8> {
+    pub fn rows(&self) -> Chunks<'_, u8> {
         self.data.chunks(self.width as usize)
     }
 
-    pub fn rows_mut(&mut self) -> ChunksMut<u8> {
+    pub fn rows_mut(&mut self) -> ChunksMut<'_, u8> {
         self.data.chunks_mut(self.width as usize)
     }
 
@@ -427,11 +529,7 @@ impl ByteGrid {
         let mut result: Vec<u8> = Vec::with_capacity(offsets.len());
         for offset in offsets.iter() {
             let new_point = point + *offset;
-            if let Some(c) = self.get_point(new_point) {
-                result.push(*c);
-            } else {
-                return None;
-            }
+            result.push(*self.get_point(new_point)?);
         }
         Some(result)
     }
@@ -443,7 +541,7 @@ impl ByteGrid {
         col: i32,
         row_offset: i32,
         col_offset: i32,
-    ) -> ByteGridIter {
+    ) -> ByteGridIter<'_> {
         ByteGridIter::new(self, row, col, row_offset, col_offset)
     }
 
@@ -453,15 +551,15 @@ impl ByteGrid {
         col: i32,
         row_offset: i32,
         col_offset: i32,
-    ) -> ByteGridIterMut {
+    ) -> ByteGridIterMut<'_> {
         ByteGridIterMut::new(self, row, col, row_offset, col_offset)
     }
 
-    pub fn iter_towards(&self, pos: Point, vel: Point) -> ByteGridIter {
+    pub fn iter_towards(&self, pos: Point, vel: Point) -> ByteGridIter<'_> {
         self.iter_from_point_with_offsets(pos.row, pos.col, vel.row, vel.col)
     }
 
-    pub fn iter_mut_towards(&mut self, pos: Point, vel: Point) -> ByteGridIterMut {
+    pub fn iter_mut_towards(&mut self, pos: Point, vel: Point) -> ByteGridIterMut<'_> {
         self.iter_mut_ray(pos.row, pos.col, vel.row, vel.col)
     }
 }
@@ -596,6 +694,100 @@ impl Region {
     pub fn sides(&self) -> i32 {
         self.sides
     }
+
+    pub fn byte(&self) -> u8 {
+        self.byte
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// The region's outline as closed polygons of cell corners, where corner
+    /// `(row, col)` is the top left of that cell. Outlines run clockwise with
+    /// the region on their right, so holes come out anticlockwise. Only the
+    /// corners where the outline turns are kept, so the total number of
+    /// points matches `sides`. Holes that touch at a corner share an outline.
+    pub fn boundaries(&self) -> Vec<Vec<Point>> {
+        let inside: HashSet<Point> = self.points.iter().copied().collect();
+        // every cell edge that faces out of the region, keyed by start corner
+        let mut edges: HashMap<Point, Vec<Point>> = HashMap::new();
+        for p in self.points.iter() {
+            let (tl, tr) = (*p, *p + EAST);
+            let (bl, br) = (*p + SOUTH, *p + SOUTHEAST);
+            for (neighbour, from, to) in [
+                (*p + NORTH, tl, tr),
+                (*p + EAST, tr, br),
+                (*p + SOUTH, br, bl),
+                (*p + WEST, bl, tl),
+            ] {
+                if !inside.contains(&neighbour) {
+                    edges.entry(from).or_default().push(to);
+                }
+            }
+        }
+        let mut starts: Vec<Point> = edges.keys().copied().collect();
+        starts.sort();
+        let mut loops: Vec<Vec<Point>> = vec![];
+        for start in starts {
+            while edges.get(&start).is_some_and(|e| !e.is_empty()) {
+                let mut corners: Vec<Point> = vec![start];
+                let mut heading: Option<Point> = None;
+                let mut at = start;
+                loop {
+                    let options = edges.get_mut(&at).unwrap();
+                    // where outlines touch diagonally, turn right to stay
+                    // hugging the same cells
+                    let pick = (0..options.len())
+                        .max_by_key(|i| {
+                            let dir = options[*i] - at;
+                            match heading {
+                                Some(h) if Point::new(h.col, -h.row) == dir => 2,
+                                Some(h) if h == dir => 1,
+                                _ => 0,
+                            }
+                        })
+                        .unwrap();
+                    let next = options.swap_remove(pick);
+                    heading = Some(next - at);
+                    at = next;
+                    if at == start {
+                        break;
+                    }
+                    corners.push(at);
+                }
+                loops.push(turning_points(&corners));
+            }
+        }
+        // outer outlines first
+        loops.sort_by_key(|l| signed_area(l) < 0);
+        loops
+    }
+}
+
+/// Drop corners that sit in the middle of a straight run
+fn turning_points(corners: &[Point]) -> Vec<Point> {
+    let n = corners.len();
+    (0..n)
+        .filter(|i| {
+            let prev = corners[(i + n - 1) % n];
+            let next = corners[(i + 1) % n];
+            corners[*i] - prev != next - corners[*i]
+        })
+        .map(|i| corners[i])
+        .collect()
+}
+
+/// Twice the signed area of a polygon, positive when it runs clockwise on
+/// screen (rows growing downwards)
+pub fn signed_area(polygon: &[Point]) -> i64 {
+    let n = polygon.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+            a.col as i64 * b.row as i64 - b.col as i64 * a.row as i64
+        })
+        .sum()
 }
 
 #[cfg(test)]
@@ -613,6 +805,24 @@ mod tests {
         assert_eq!(new_point.col, 2);
     }
 
+    #[test]
+    fn test_direction() {
+        assert_eq!(Direction::North.turn_right(), Direction::East);
+        assert_eq!(Direction::North.turn_left(), Direction::West);
+        assert_eq!(Direction::West.turn_around(), Direction::East);
+        assert_eq!(Direction::NorthWest.rotate(1), Direction::North);
+        assert_eq!(Direction::SouthWest.to_point(), SOUTH + WEST);
+        assert_eq!(Direction::from_point(NORTHEAST), Some(Direction::NorthEast));
+        assert_eq!(Direction::from_point(EAST * 2), None);
+        for b in *b"vSD" {
+            assert_eq!(Direction::from_byte(b), Some(Direction::South));
+        }
+        assert_eq!(Direction::from_byte(b'x'), None);
+        let arrows: Vec<u8> = Direction::CARDINALS.map(Direction::to_arrow).to_vec();
+        assert_eq!(arrows, b"^>v<");
+        assert!(!Direction::SouthEast.is_cardinal());
+    }
+
     #[test]
     fn test_u8_grid() {
         let mut grid = ByteGrid::new(
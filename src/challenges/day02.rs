@@ -1,6 +1,6 @@
 use advent24::parse_number_list;
 use clap;
-use std::{fs, str};
+use std::{fs, ops::RangeInclusive, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -9,6 +9,28 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// How many levels may be removed to make a report safe (1 with --part2)
+    #[arg(long)]
+    tolerance: Option<usize>,
+
+    /// Smallest allowed difference between adjacent levels
+    #[arg(long, default_value_t = 1)]
+    min_step: i64,
+
+    /// Largest allowed difference between adjacent levels
+    #[arg(long, default_value_t = 3)]
+    max_step: i64,
+
+    /// Explain every report that isn't safe as-is
+    #[clap(long, action)]
+    explain: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Increasing,
+    Decreasing,
 }
 
 #[derive(Debug, PartialEq)]
@@ -20,66 +42,204 @@ enum PlantState {
 }
 
 impl PlantState {
-    fn next_state(&self, next: i64) -> Self {
+    fn next_state(&self, next: i64, steps: &RangeInclusive<i64>) -> Self {
         match self {
-            Self::Initial(num) | Self::SafeIncreasing(num) if (next > *num && next <= *num + 3) => {
+            Self::Initial(num) | Self::SafeIncreasing(num) if steps.contains(&(next - num)) => {
                 Self::SafeIncreasing(next)
             }
-            Self::Initial(num) | Self::SafeDecreasing(num) if (next < *num && next >= *num - 3) => {
+            Self::Initial(num) | Self::SafeDecreasing(num) if steps.contains(&(num - next)) => {
                 Self::SafeDecreasing(next)
             }
             _ => Self::Unsafe,
         }
     }
+
+    fn trend(&self) -> Option<Trend> {
+        match self {
+            Self::SafeIncreasing(_) => Some(Trend::Increasing),
+            Self::SafeDecreasing(_) => Some(Trend::Decreasing),
+            _ => None,
+        }
+    }
 }
 
-fn part1(input: String) -> i64 {
-    let mut safe = 0;
-    for line in input.lines() {
-        let digits: Vec<i64> = parse_number_list(line);
-        let state = digits[1..]
-            .iter()
-            .fold(PlantState::Initial(digits[0]), |s, item| {
-                s.next_state(*item)
-            });
-        safe += match state {
-            PlantState::SafeIncreasing(_) | PlantState::SafeDecreasing(_) => 1,
-            _ => 0,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pub steps: RangeInclusive<i64>,
+    /// How many levels may be removed
+    pub tolerance: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            steps: 1..=3,
+            tolerance: 0,
         }
     }
-    safe
 }
 
-fn part2(input: String) -> i64 {
-    let mut safe = 0;
-    for line in input.lines() {
-        let digits: Vec<i64> = parse_number_list(line);
-        for one_to_skip in 0..digits.len() {
-            let mut new_digits = vec![];
-            for (i, n) in digits.iter().enumerate() {
-                if i != one_to_skip {
-                    new_digits.push(*n);
-                }
-            }
-            let state = new_digits[1..]
-                .iter()
-                .fold(PlantState::Initial(new_digits[0]), |s, item| {
-                    s.next_state(*item)
-                });
-            if state != PlantState::Unsafe {
-                safe += 1;
-                break;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// Safe without removing anything
+    pub safe: bool,
+    /// The direction the report was heading before it went wrong, if it
+    /// got past the first level
+    pub trend: Option<Trend>,
+    /// Index of the level that made the report unsafe
+    pub first_violation: Option<usize>,
+    /// The fewest level indices to remove to make the report safe, or `None`
+    /// if that takes more than the tolerance
+    pub removals: Option<Vec<usize>>,
+}
+
+impl Analysis {
+    pub fn is_tolerable(&self) -> bool {
+        self.removals.is_some()
+    }
+}
+
+/// The fewest removals that leave levels moving in `trend` by allowed steps.
+///
+/// `best[i]` is the fewest removals among `levels[..=i]` when `i` is kept, and
+/// a kept level can only follow one of the `tolerance + 1` levels before it,
+/// so this is linear in the report length for a fixed tolerance.
+fn min_removals(levels: &[i64], trend: Trend, rules: &Rules) -> Option<Vec<usize>> {
+    let allowed = |from: i64, to: i64| match trend {
+        Trend::Increasing => rules.steps.contains(&(to - from)),
+        Trend::Decreasing => rules.steps.contains(&(from - to)),
+    };
+    let k = rules.tolerance;
+    // (removals, previous kept index)
+    let mut best: Vec<Option<(usize, Option<usize>)>> = vec![None; levels.len()];
+    for i in 0..levels.len() {
+        if i <= k {
+            best[i] = Some((i, None));
+        }
+        for j in i.saturating_sub(k + 1)..i {
+            let Some((removed, _)) = best[j] else {
+                continue;
+            };
+            let removed = removed + i - j - 1;
+            if removed <= k
+                && allowed(levels[j], levels[i])
+                && best[i].is_none_or(|(r, _)| removed < r)
+            {
+                best[i] = Some((removed, Some(j)));
             }
         }
     }
-    safe
+    let (last, _) = (0..levels.len())
+        .filter_map(|i| Some((i, best[i]?.0 + levels.len() - 1 - i)))
+        .filter(|(_, removed)| *removed <= k)
+        .min_by_key(|(_, removed)| *removed)?;
+    let mut kept: Vec<bool> = vec![false; levels.len()];
+    let mut at = last;
+    kept[at] = true;
+    while let Some((_, Some(prev))) = best[at] {
+        at = prev;
+        kept[at] = true;
+    }
+    Some((0..levels.len()).filter(|i| !kept[*i]).collect())
+}
+
+pub fn analyse(levels: &[i64], rules: &Rules) -> Analysis {
+    let mut state = PlantState::Initial(levels.first().copied().unwrap_or_default());
+    let mut trend = None;
+    let mut first_violation = None;
+    for (i, level) in levels.iter().enumerate().skip(1) {
+        state = state.next_state(*level, &rules.steps);
+        if state == PlantState::Unsafe {
+            first_violation = Some(i);
+            break;
+        }
+        trend = state.trend();
+    }
+    let removals = if first_violation.is_none() {
+        Some(vec![])
+    } else {
+        [Trend::Increasing, Trend::Decreasing]
+            .into_iter()
+            .filter_map(|t| min_removals(levels, t, rules))
+            .min_by_key(Vec::len)
+    };
+    Analysis {
+        safe: first_violation.is_none(),
+        trend,
+        first_violation,
+        removals,
+    }
+}
+
+pub fn count_tolerable(input: &str, rules: &Rules, explain: bool) -> usize {
+    let mut count = 0;
+    for line in input.lines() {
+        let levels: Vec<i64> = parse_number_list(line);
+        if levels.is_empty() {
+            continue;
+        }
+        let analysis = analyse(&levels, rules);
+        if explain && !analysis.safe {
+            println!(
+                "{}: heading {:?}, broke at index {:?}, fix by removing {:?}",
+                line, analysis.trend, analysis.first_violation, analysis.removals
+            );
+        }
+        if analysis.is_tolerable() {
+            count += 1;
+        }
+    }
+    count
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    let result: i64 = match args.part2 {
-        true => part2(input),
-        false => part1(input),
+    let rules = Rules {
+        steps: args.min_step..=args.max_step,
+        tolerance: args.tolerance.unwrap_or(if args.part2 { 1 } else { 0 }),
     };
+    let result = count_tolerable(&input, &rules, args.explain);
     println!("{}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_REPORTS: &str = "\
+7 6 4 2 1
+1 2 7 8 9
+9 7 6 2 1
+1 3 2 4 5
+8 6 4 4 1
+1 3 6 7 9";
+
+    #[test]
+    fn test_day2() {
+        let strict = Rules::default();
+        let lenient = Rules {
+            tolerance: 1,
+            ..Rules::default()
+        };
+        assert_eq!(count_tolerable(TEST_REPORTS, &strict, false), 2);
+        assert_eq!(count_tolerable(TEST_REPORTS, &lenient, false), 4);
+
+        let analysis = analyse(&[1, 3, 2, 4, 5], &lenient);
+        assert!(!analysis.safe);
+        assert_eq!(analysis.trend, Some(Trend::Increasing));
+        assert_eq!(analysis.first_violation, Some(2));
+        assert_eq!(analysis.removals, Some(vec![2]));
+
+        // removing the first level is the only fix
+        assert_eq!(analyse(&[9, 1, 2, 3], &lenient).removals, Some(vec![0]));
+        assert_eq!(analyse(&[1, 2, 7, 8, 9], &lenient).removals, None);
+        let tolerant = Rules {
+            steps: 1..=3,
+            tolerance: 2,
+        };
+        assert_eq!(
+            analyse(&[1, 2, 7, 8, 3, 4], &tolerant).removals,
+            Some(vec![2, 3])
+        );
+    }
+}
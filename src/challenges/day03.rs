@@ -1,6 +1,4 @@
-use advent24::parse_number_list;
 use clap;
-use regex::Regex;
 use std::{fs, str};
 
 #[derive(clap::Args, Debug)]
@@ -10,50 +8,297 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Extra instructions to recognise, as NAME:EFFECT[:ARITY] where EFFECT is
+    /// one of product, sum, enable or disable (e.g. `add:sum`)
+    #[arg(long, value_delimiter = ',')]
+    extra: Vec<Spec>,
+
+    /// Print every recognised instruction and what it contributed
+    #[clap(long, action)]
+    trace: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Product,
+    Sum,
+    Enable,
+    Disable,
 }
 
-fn challenge(input: String) -> (i64, i64) {
-    let multiply = Regex::new(r"mul\((\d+),(\d+)\)").unwrap();
-    let do_instr = Regex::new(r"do\(\)").unwrap();
-    let dont_instr = Regex::new(r"don't\(\)").unwrap();
+/// An instruction shaped like `name(1,2,...)` taking `arity` numbers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spec {
+    pub name: String,
+    pub effect: Effect,
+    pub arity: usize,
+}
+
+impl Spec {
+    pub fn new(name: &str, effect: Effect) -> Self {
+        let arity = match effect {
+            Effect::Product | Effect::Sum => 2,
+            Effect::Enable | Effect::Disable => 0,
+        };
+        Spec {
+            name: name.to_owned(),
+            effect,
+            arity,
+        }
+    }
 
-    let cleaned = multiply.replace_all(&input, "\nVALID: mul $1 $2\n");
-    let cleaned2 = do_instr.replace_all(&cleaned, "\nVALID: enable\n");
-    let cleaned3 = dont_instr.replace_all(&cleaned2, "\nVALID: disable\n");
+    pub fn with_arity(mut self, arity: usize) -> Self {
+        self.arity = arity;
+        self
+    }
+}
 
-    let mut enabled = true;
-    let mut part1 = 0;
-    let mut part2 = 0;
+impl str::FromStr for Spec {
+    type Err = String;
 
-    for line in cleaned3.lines() {
-        if line.len() < 8 {
-            continue;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts
+            .next()
+            .filter(|n| !n.is_empty())
+            .ok_or("missing name")?;
+        let effect = match parts.next() {
+            Some("product") => Effect::Product,
+            Some("sum") => Effect::Sum,
+            Some("enable") => Effect::Enable,
+            Some("disable") => Effect::Disable,
+            other => return Err(format!("unknown effect {:?}", other)),
+        };
+        let spec = Spec::new(name, effect);
+        match parts.next() {
+            Some(arity) => Ok(spec.with_arity(arity.parse().map_err(|_| "bad arity")?)),
+            None => Ok(spec),
         }
-        match &line[0..8] {
-            "VALID: m" => {
-                let parts: Vec<i64> = parse_number_list(line);
-                if enabled {
-                    part2 += parts[0] * parts[1];
+    }
+}
+
+/// The instructions from the puzzle: `mul(a,b)`, `do()` and `don't()`
+pub fn default_specs() -> Vec<Spec> {
+    vec![
+        Spec::new("mul", Effect::Product),
+        Spec::new("do", Effect::Enable),
+        Spec::new("don't", Effect::Disable),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// Byte offset of the instruction name in the input
+    pub offset: usize,
+    pub len: usize,
+    /// Index into the specs the tokenizer was built with
+    pub spec: usize,
+    pub args: Vec<i64>,
+}
+
+/// Finds well formed instructions in corrupted memory, skipping everything else
+pub struct Tokenizer<'a> {
+    input: &'a [u8],
+    specs: &'a [Spec],
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a str, specs: &'a [Spec]) -> Self {
+        Tokenizer {
+            input: input.as_bytes(),
+            specs,
+            pos: 0,
+        }
+    }
+
+    /// The arguments and end offset of `spec` if it's at `start`
+    fn match_at(&self, spec: &Spec, start: usize) -> Option<(Vec<i64>, usize)> {
+        let rest = &self.input[start..];
+        let mut i = spec.name.len();
+        if !rest.starts_with(spec.name.as_bytes()) || rest.get(i) != Some(&b'(') {
+            return None;
+        }
+        i += 1;
+        let mut args: Vec<i64> = Vec::with_capacity(spec.arity);
+        for n in 0..spec.arity {
+            if n > 0 {
+                if rest.get(i) != Some(&b',') {
+                    return None;
                 }
-                part1 += parts[0] * parts[1];
+                i += 1;
             }
-            "VALID: d" => {
-                enabled = false;
+            let digits = rest[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 {
+                return None;
             }
-            "VALID: e" => {
-                enabled = true;
+            args.push(str::from_utf8(&rest[i..i + digits]).ok()?.parse().ok()?);
+            i += digits;
+        }
+        if rest.get(i) != Some(&b')') {
+            return None;
+        }
+        Some((args, start + i + 1))
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Instruction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.input.len() {
+            let start = self.pos;
+            for (idx, spec) in self.specs.iter().enumerate() {
+                if let Some((args, end)) = self.match_at(spec, start) {
+                    self.pos = end;
+                    return Some(Instruction {
+                        offset: start,
+                        len: end - start,
+                        spec: idx,
+                        args,
+                    });
+                }
             }
-            _ => {}
+            self.pos += 1;
         }
+        None
     }
+}
 
-    (part1, part2)
+/// One executed instruction, with its value and whether it was counted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub instruction: Instruction,
+    pub enabled: bool,
+    pub value: i64,
+}
+
+impl Step {
+    /// What this step added to the conditional (part 2) total
+    pub fn contribution(&self) -> i64 {
+        if self.enabled {
+            self.value
+        } else {
+            0
+        }
+    }
+
+    pub fn describe(&self, specs: &[Spec]) -> String {
+        let args: Vec<String> = self.instruction.args.iter().map(i64::to_string).collect();
+        let state = if self.enabled { "" } else { " (disabled)" };
+        format!(
+            "@{}: {}({}) -> {}{}",
+            self.instruction.offset,
+            specs[self.instruction.spec].name,
+            args.join(","),
+            self.contribution(),
+            state
+        )
+    }
+}
+
+pub struct Machine<'a> {
+    specs: &'a [Spec],
+    pub enabled: bool,
+    /// Sum of every arithmetic instruction, ignoring enable/disable
+    pub total: i64,
+    /// Sum of arithmetic instructions while enabled
+    pub enabled_total: i64,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(specs: &'a [Spec]) -> Self {
+        Machine {
+            specs,
+            enabled: true,
+            total: 0,
+            enabled_total: 0,
+        }
+    }
+
+    pub fn execute(&mut self, instruction: Instruction) -> Step {
+        let value = match self.specs[instruction.spec].effect {
+            Effect::Product => instruction.args.iter().product(),
+            Effect::Sum => instruction.args.iter().sum(),
+            Effect::Enable => {
+                self.enabled = true;
+                0
+            }
+            Effect::Disable => {
+                self.enabled = false;
+                0
+            }
+        };
+        let step = Step {
+            instruction,
+            enabled: self.enabled,
+            value,
+        };
+        self.total += value;
+        self.enabled_total += step.contribution();
+        step
+    }
+
+    pub fn run(&mut self, input: &str) -> Vec<Step> {
+        Tokenizer::new(input, self.specs)
+            .map(|instruction| self.execute(instruction))
+            .collect()
+    }
+}
+
+fn challenge(input: &str, specs: &[Spec], trace: bool) -> (i64, i64) {
+    let mut machine = Machine::new(specs);
+    for step in machine.run(input) {
+        if trace {
+            println!("{}", step.describe(specs));
+        }
+    }
+    (machine.total, machine.enabled_total)
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    let (part1, part2) = challenge(input);
+    let mut specs = default_specs();
+    specs.extend(args.extra.iter().cloned());
+    let (part1, part2) = challenge(&input, &specs, args.trace);
 
     println!("part1: {}", part1);
     println!("part2: {}", part2);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MEMORY: &str =
+        "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    #[test]
+    fn test_day3() {
+        let specs = default_specs();
+        assert_eq!(challenge(TEST_MEMORY, &specs, false), (161, 48));
+
+        let found: Vec<Instruction> = Tokenizer::new(TEST_MEMORY, &specs).collect();
+        assert_eq!(found.len(), 6);
+        assert_eq!(
+            found[0],
+            Instruction {
+                offset: 1,
+                len: 8,
+                spec: 0,
+                args: vec![2, 4]
+            }
+        );
+        let steps = Machine::new(&specs).run(TEST_MEMORY);
+        assert_eq!(steps[2].describe(&specs), "@28: mul(5,5) -> 0 (disabled)");
+
+        let mut specs = default_specs();
+        specs.push("add:sum:3".parse().unwrap());
+        specs.push("off:disable".parse().unwrap());
+        let mut machine = Machine::new(&specs);
+        machine.run("add(1,2,3)add(1,2)mul(2,3)off()mul(4,4)");
+        assert_eq!((machine.total, machine.enabled_total), (28, 12));
+        assert!("add:times".parse::<Spec>().is_err());
+    }
+}
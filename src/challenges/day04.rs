@@ -1,4 +1,6 @@
-use advent24::{string_to_2d_array, Tile};
+use advent24::bytegrid::{
+    ByteGrid, Point, EAST, NORTH, NORTHEAST, NORTHWEST, SOUTH, SOUTHEAST, SOUTHWEST, WEST,
+};
 use clap;
 use std::{fs, str};
 
@@ -11,87 +13,57 @@ pub struct Args {
     part2: bool,
 }
 
-pub fn part1(args: &Args) -> u64 {
-    let input = fs::read_to_string(&args.file).expect("I/O error");
-    let mut world = string_to_2d_array(input);
-
-    find_word_any_direction(&mut world, "XMAS")
-}
+const DIRECTIONS: [Point; 8] = [
+    EAST, SOUTHEAST, SOUTH, SOUTHWEST, WEST, NORTHWEST, NORTH, NORTHEAST,
+];
 
-fn check_direction(
-    tile: Tile,
-    world: &[Vec<char>],
-    direction_func: fn(&Tile) -> Option<Tile>,
-    word: &str,
-) -> u64 {
-    let mut word_iter = word.chars();
-    let first_char = tile.char_at(world).unwrap();
-    if first_char != word_iter.next().unwrap() {
-        return 0;
-    }
-    let mut t = tile;
-    let mut len_checked = 1;
-    while let Some(next_tile) = direction_func(&t) {
-        let wordc_opt = word_iter.next();
-        if wordc_opt.is_none() {
-            break;
+fn find_word_any_direction(world: &ByteGrid, word: &str) -> u64 {
+    let word = word.as_bytes();
+    let mut count = 0;
+    for (idx, b) in world.data.iter().enumerate() {
+        if Some(b) != word.first() {
+            continue;
         }
-        let wordc = wordc_opt.unwrap();
-        let worldc = next_tile.char_at(world).unwrap();
-        if worldc != wordc {
-            return 0;
+        let point = world.idx_to_point(idx);
+        for direction in DIRECTIONS {
+            if world
+                .iter_towards(point, direction)
+                .take(word.len())
+                .eq(word.iter().copied())
+            {
+                count += 1;
+            }
         }
-        len_checked += 1;
-        t = next_tile;
-    }
-    if len_checked == word.len() {
-        1
-    } else {
-        0
     }
+    count
 }
 
-fn find_word_any_direction(world: &mut [Vec<char>], word: &str) -> u64 {
-    let mut count = 0;
-    for (y, line) in world.iter().enumerate() {
-        for (x, _) in line.iter().enumerate() {
-            let tile = Tile::from_world(world, x, y);
-            count += check_direction(tile, world, Tile::right, word);
-            count += check_direction(tile, world, Tile::bottomright, word);
-            count += check_direction(tile, world, Tile::bottom, word);
-            count += check_direction(tile, world, Tile::bottomleft, word);
-            count += check_direction(tile, world, Tile::left, word);
-            count += check_direction(tile, world, Tile::topleft, word);
-            count += check_direction(tile, world, Tile::top, word);
-            count += check_direction(tile, world, Tile::topright, word);
+fn count_x_mas(world: &ByteGrid) -> u64 {
+    let corners = [NORTHWEST, NORTHEAST, SOUTHWEST, SOUTHEAST];
+    let mut xmas_count = 0;
+    for (idx, b) in world.data.iter().enumerate() {
+        if *b != b'A' {
+            continue;
+        }
+        let Some(surrounding) = world.u8s_surrounding(world.idx_to_point(idx), &corners) else {
+            continue;
+        };
+        xmas_count += match surrounding.as_slice() {
+            b"MMSS" | b"MSMS" | b"SMSM" | b"SSMM" => 1,
+            _ => 0,
         }
     }
-    count
+    xmas_count
+}
+
+pub fn part1(args: &Args) -> u64 {
+    let input = fs::read_to_string(&args.file).expect("I/O error");
+    find_word_any_direction(&ByteGrid::new(&input), "XMAS")
 }
 
 pub fn part2(args: &Args) -> u64 {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    let world = string_to_2d_array(input);
-    let mut xmas_count = 0;
-    for (y, line) in world[1..world.len() - 1].iter().enumerate() {
-        for (x, c) in line[1..line.len() - 1].iter().enumerate() {
-            if *c != 'A' {
-                continue;
-            }
-            let topleft = world[y][x];
-            let topright = world[y][x + 2];
-            let bottomleft = world[y + 2][x];
-            let bottomright = world[y + 2][x + 2];
-            xmas_count += match (topleft, topright, bottomleft, bottomright) {
-                ('M', 'M', 'S', 'S') => 1,
-                ('M', 'S', 'M', 'S') => 1,
-                ('S', 'M', 'S', 'M') => 1,
-                ('S', 'S', 'M', 'M') => 1,
-                _ => 0,
-            }
-        }
-    }
-    xmas_count
+    count_x_mas(&ByteGrid::new(&input))
 }
 
 pub fn entrypoint(args: &Args) {
@@ -103,3 +75,16 @@ pub fn entrypoint(args: &Args) {
         println!("{}", res);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day4() {
+        let input = fs::read_to_string("./inputs/day4/sample.txt").unwrap();
+        let world = ByteGrid::new(&input);
+        assert_eq!(find_word_any_direction(&world, "XMAS"), 18);
+        assert_eq!(count_x_mas(&world), 9);
+    }
+}
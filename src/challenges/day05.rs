@@ -1,8 +1,8 @@
+use advent24::graph::{reorder, violations};
 use advent24::parse_number_list_delimited_by;
 use clap;
-use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::{fs, str};
+use std::{fmt, fs, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -11,69 +11,205 @@ pub struct Args {
     /// Find "gear ratios"
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Report broken rules, corrections and ambiguities for every update
+    #[clap(long, action)]
+    explain: bool,
 }
 
-pub fn parse_graph(s: &str) -> HashMap<u64, Vec<u64>> {
+/// Rules as a graph where `X|Y` is an edge `X -> Y` (X prints before Y)
+pub fn parse_rules(s: &str) -> HashMap<u64, Vec<u64>> {
     let mut res: HashMap<u64, Vec<u64>> = HashMap::new();
     for line in s.lines() {
-        let split: Vec<&str> = line.split('|').collect();
-        if split.len() == 2 {
-            res.entry(split[1].parse().unwrap())
+        if let Some((before, after)) = line.split_once('|') {
+            res.entry(before.parse().unwrap())
                 .or_default()
-                .push(split[0].parse().unwrap());
+                .push(after.parse().unwrap());
         }
     }
     res
 }
 
-pub fn entrypoint(args: &Args) {
-    let input = fs::read_to_string(&args.file).expect("I/O error");
-    let graph = parse_graph(&input);
-    let updates: Vec<Vec<u64>> = input
-        .lines()
-        .filter(|l| l.contains(","))
+pub fn parse_updates(s: &str) -> Vec<Vec<u64>> {
+    s.lines()
+        .filter(|l| l.contains(','))
         .map(|l| parse_number_list_delimited_by(l, ","))
-        .collect();
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub before: u64,
+    pub after: u64,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} must come before {}", self.before, self.after)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub update: Vec<u64>,
+    /// Every rule the update breaks
+    pub violations: Vec<Rule>,
+    /// The update sorted to obey the rules between its pages, or a cycle
+    /// among them if no order can
+    pub ordering: Result<Vec<u64>, Vec<u64>>,
+    /// Neighbouring pages in `ordering` with no rule between them, which
+    /// could just as well be swapped
+    pub ambiguous: Vec<(u64, u64)>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Whether the rules allow exactly one order of the pages
+    pub fn is_determined(&self) -> bool {
+        self.ordering.is_ok() && self.ambiguous.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_valid() {
+            write!(f, "valid: {:?}", self.update)?;
+        } else {
+            write!(f, "invalid: {:?}", self.update)?;
+            for rule in self.violations.iter() {
+                write!(f, "\n  broken: {}", rule)?;
+            }
+            match &self.ordering {
+                Ok(order) => write!(f, "\n  corrected: {:?}", order)?,
+                Err(cycle) => write!(f, "\n  no order possible, cycle: {:?}", cycle)?,
+            }
+        }
+        for (a, b) in self.ambiguous.iter() {
+            write!(f, "\n  no rule orders {} and {}", a, b)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Validator {
+    rules: HashMap<u64, Vec<u64>>,
+}
+
+impl Validator {
+    pub fn new(input: &str) -> Self {
+        Validator {
+            rules: parse_rules(input),
+        }
+    }
+
+    fn has_rule(&self, before: u64, after: u64) -> bool {
+        self.rules
+            .get(&before)
+            .is_some_and(|successors| successors.contains(&after))
+    }
+
+    pub fn check(&self, update: &[u64]) -> Report {
+        let violations = violations(&self.rules, update)
+            .into_iter()
+            .map(|(before, after)| Rule { before, after })
+            .collect();
+        let ordering = reorder(&self.rules, update);
+        // a topological order is the only one exactly when every neighbouring
+        // pair is tied together by a rule
+        let ambiguous = match &ordering {
+            Ok(order) => order
+                .windows(2)
+                .filter(|w| !self.has_rule(w[0], w[1]))
+                .map(|w| (w[0], w[1]))
+                .collect(),
+            Err(_) => vec![],
+        };
+        Report {
+            update: update.to_vec(),
+            violations,
+            ordering,
+            ambiguous,
+        }
+    }
+}
+
+/// Sum of middle pages of the valid updates, and of the corrected invalid ones
+pub fn solve(input: &str, verbose: bool) -> (u64, u64) {
+    let validator = Validator::new(input);
     let mut part1 = 0;
     let mut part2 = 0;
-    println!("{:?}", graph);
-    for update in updates.iter() {
-        let mut is_valid = true;
-        'outer: for (i, n) in update.iter().enumerate() {
-            if let Some(req_predecessors) = graph.get(n) {
-                let actual_predecessors = &update[..i + 1];
-                for p in req_predecessors.iter() {
-                    if update.contains(p) && !actual_predecessors.contains(p) {
-                        is_valid = false;
-                        break 'outer;
-                    }
-                }
-            }
+    for update in parse_updates(input) {
+        let report = validator.check(&update);
+        if verbose {
+            println!("{}", report);
         }
-        if is_valid {
-            println!("valid: {:?}", update);
+        if report.is_valid() {
             part1 += update[update.len() / 2];
-        } else {
-            println!("invalid: {:?}", update);
-            let mut correct: Vec<u64> = update.clone();
-            correct.sort_by(|a, b| {
-                if let Some(preds) = graph.get(a) {
-                    if preds.contains(b) {
-                        return Ordering::Greater;
-                    }
-                }
-                match graph.get(b) {
-                    Some(preds) if preds.contains(a) => {
-                        Ordering::Less
-                    }
-                    _ => Ordering::Equal,
-                }
-            });
-            println!("corrected: {:?}", correct);
+        } else if let Ok(correct) = &report.ordering {
+            if verbose && !report.is_determined() {
+                println!("  the middle page depends on how ties are broken");
+            }
             part2 += correct[correct.len() / 2];
         }
     }
+    (part1, part2)
+}
 
+pub fn entrypoint(args: &Args) {
+    let input = fs::read_to_string(&args.file).expect("I/O error");
+    let (part1, part2) = solve(&input, args.explain);
     println!("part1: {}", part1);
     println!("part2: {}", part2);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day5() {
+        let input = fs::read_to_string("./inputs/day5/sample.txt").unwrap();
+        assert_eq!(solve(&input, false), (143, 123));
+
+        let validator = Validator::new(&input);
+        let report = validator.check(&[97, 13, 75, 29, 47]);
+        assert!(!report.is_valid());
+        assert_eq!(report.ordering, Ok(vec![97, 75, 47, 29, 13]));
+        assert!(report.is_determined());
+        assert!(report.violations.contains(&Rule {
+            before: 29,
+            after: 13
+        }));
+        assert_eq!(report.violations.len(), 4);
+        assert_eq!(
+            Rule {
+                before: 47,
+                after: 53
+            }
+            .to_string(),
+            "47 must come before 53"
+        );
+        assert!(validator.check(&[75, 47, 61, 53, 29]).is_valid());
+    }
+
+    #[test]
+    fn test_day5_problems() {
+        let validator = Validator::new("1|2\n2|3\n3|1\n4|5\n4|6\n");
+        let report = validator.check(&[3, 2, 1]);
+        let Err(mut cycle) = report.ordering.clone() else {
+            panic!("expected a cycle");
+        };
+        cycle.sort();
+        assert_eq!(cycle, vec![1, 2, 3]);
+        assert!(!report.is_determined());
+
+        // 5 and 6 both follow 4, but nothing says which comes first
+        let report = validator.check(&[4, 6, 5]);
+        assert!(report.is_valid());
+        assert_eq!(report.ordering, Ok(vec![4, 5, 6]));
+        assert_eq!(report.ambiguous, vec![(5, 6)]);
+    }
+}
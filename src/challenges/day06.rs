@@ -1,6 +1,6 @@
-use advent24::{print_2d_array, string_to_2d_array, Tile};
+use advent24::bytegrid::{ByteGrid, Direction, Point};
 use clap;
-use std::{fs, str};
+use std::{collections::HashSet, fs, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -9,141 +9,240 @@ pub struct Args {
     /// Find "gear ratios"
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Print the lab with the guard's path (and loop-causing obstacles)
+    #[clap(long, action)]
+    show: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Guard {
+    pub pos: Point,
+    pub heading: Direction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Walk {
+    /// Every cell the guard stood on, in the order first visited
+    pub visited: Vec<Point>,
+    /// The guard's state just before first stepping onto each visited cell,
+    /// `None` for the starting cell
+    pub entered_from: Vec<Option<Guard>>,
+    pub loops: bool,
 }
 
-struct Character {
-    pub current_pos: Tile,
-    pub next_step: fn(&Tile) -> Option<Tile>,
-    pub next_step_char: char,
+/// Index of a cardinal direction into per-direction tables
+fn slot(heading: Direction) -> usize {
+    heading as usize / 2
 }
 
-impl Character {
-    fn char_to_next_step(c: char) -> Option<fn(&Tile) -> Option<Tile>> {
-        match c {
-            '^' => Some(Tile::top),
-            '>' => Some(Tile::right),
-            '<' => Some(Tile::left),
-            'v' => Some(Tile::bottom),
-            _ => None,
+pub struct Lab {
+    pub grid: ByteGrid,
+    pub start: Guard,
+    /// For every cell and heading, where a guard there stops in front of the
+    /// next obstacle, or `None` if it walks off the map
+    stops: Vec<[Option<Point>; 4]>,
+}
+
+impl Lab {
+    pub fn new(input: &str) -> Self {
+        let grid = ByteGrid::new(input);
+        let (idx, heading) = grid
+            .data
+            .iter()
+            .enumerate()
+            .find_map(|(idx, b)| Some((idx, Direction::from_byte(*b)?)))
+            .expect("no guard");
+        let start = Guard {
+            pos: grid.idx_to_point(idx),
+            heading,
+        };
+        let mut stops: Vec<[Option<Point>; 4]> = vec![[None; 4]; grid.data.len()];
+        for heading in Direction::CARDINALS {
+            // fill in cells so the neighbour ahead is always done first
+            let order: Box<dyn Iterator<Item = usize>> = match heading {
+                Direction::East | Direction::South => Box::new((0..grid.data.len()).rev()),
+                _ => Box::new(0..grid.data.len()),
+            };
+            for idx in order {
+                let pos = grid.idx_to_point(idx);
+                let ahead = pos + heading.to_point();
+                stops[idx][slot(heading)] = match grid.get_point(ahead) {
+                    None => None,
+                    Some(b'#') => Some(pos),
+                    Some(_) => stops[grid.point_to_idx(ahead)][slot(heading)],
+                };
+            }
         }
+        Lab { grid, start, stops }
     }
 
-    fn rotate_right(&mut self) -> &mut Self {
-        if self.next_step_char == '^' {
-            self.next_step = Tile::right;
-            self.next_step_char = '>';
-        } else if self.next_step_char == '>' {
-            self.next_step = Tile::bottom;
-            self.next_step_char = 'v';
-        } else if self.next_step_char == 'v' {
-            self.next_step = Tile::left;
-            self.next_step_char = '<';
-        } else if self.next_step_char == '<' {
-            self.next_step = Tile::top;
-            self.next_step_char = '^';
-        }
-        self
+    fn is_obstacle(&self, p: Point, extra: Option<Point>) -> bool {
+        Some(p) == extra || self.grid.get_point(p) == Some(&b'#')
     }
 
-    fn take_step(&mut self, world: &mut [Vec<char>]) -> Option<u64> {
-        if let Some(tile) = (self.next_step)(&self.current_pos) {
-            match world[tile.y][tile.x] {
-                '#' | 'O' => {
-                    self.rotate_right();
-                    Some(0)
-                }
-                '.' | '1' | '2' | '3' | '4' => {
-                    let mut tile_visited = 0;
-                    match world[self.current_pos.y][self.current_pos.x] {
-                        '.' | '^' | '>' | 'v' | '<' => {
-                            world[self.current_pos.y][self.current_pos.x] = '1';
-                            tile_visited = 1;
-                        }
-                        '1' => {
-                            world[self.current_pos.y][self.current_pos.x] = '2';
-                        }
-                        '2' => {
-                            world[self.current_pos.y][self.current_pos.x] = '3';
-                        }
-                        '3' => {
-                            world[self.current_pos.y][self.current_pos.x] = '4';
-                        }
-                        '4' => {
-                            world[self.current_pos.y][self.current_pos.x] = '5';
-                            return None;
-                        }
-                        _ => return None,
-                    }
-                    self.current_pos = tile;
-                    Some(tile_visited)
+    /// Walk one cell at a time from the start until the guard leaves or
+    /// repeats a position and heading
+    pub fn walk(&self) -> Walk {
+        let mut seen: Vec<[bool; 4]> = vec![[false; 4]; self.grid.data.len()];
+        let mut visited: Vec<Point> = vec![self.start.pos];
+        let mut entered_from: Vec<Option<Guard>> = vec![None];
+        let mut guard = self.start;
+        loop {
+            let idx = self.grid.point_to_idx(guard.pos);
+            if seen[idx][slot(guard.heading)] {
+                return Walk {
+                    visited,
+                    entered_from,
+                    loops: true,
+                };
+            }
+            if seen[idx].iter().all(|s| !s) && guard.pos != self.start.pos {
+                visited.push(guard.pos);
+            }
+            seen[idx][slot(guard.heading)] = true;
+            let ahead = guard.pos + guard.heading.to_point();
+            if !self.grid.is_valid_point(ahead) {
+                return Walk {
+                    visited,
+                    entered_from,
+                    loops: false,
+                };
+            }
+            if self.is_obstacle(ahead, None) {
+                guard.heading = guard.heading.turn_right();
+            } else {
+                if !seen[self.grid.point_to_idx(ahead)].iter().any(|s| *s)
+                    && ahead != self.start.pos
+                {
+                    entered_from.push(Some(guard));
                 }
-                _ => None,
+                guard.pos = ahead;
             }
-        } else {
-            world[self.current_pos.y][self.current_pos.x] = 'X';
-            None
         }
     }
-}
 
-pub fn run_simulation(world: &mut [Vec<char>]) -> u64 {
-    let mut character = Character {
-        current_pos: Tile::from_world(world, 0, 0),
-        next_step: Tile::top,
-        next_step_char: '^',
-    };
-    'outer: for (y, line) in world.iter().enumerate() {
-        for (x, c) in line.iter().enumerate() {
-            if let Some(valid_next_step) = Character::char_to_next_step(*c) {
-                character.next_step = valid_next_step;
-                character.current_pos.x = x;
-                character.current_pos.y = y;
-                break 'outer;
+    /// Where the guard stops next, taking an extra obstacle into account
+    fn next_stop(&self, guard: Guard, extra: Point) -> Option<Point> {
+        let table = self.stops[self.grid.point_to_idx(guard.pos)][slot(guard.heading)];
+        let step = guard.heading.to_point();
+        let diff = extra - guard.pos;
+        // how many steps ahead the extra obstacle is, if it's in the way at all
+        let distance = match guard.heading {
+            Direction::North | Direction::South if diff.col == 0 => diff.row * step.row,
+            Direction::East | Direction::West if diff.row == 0 => diff.col * step.col,
+            _ => 0,
+        };
+        if distance < 1 {
+            return table;
+        }
+        let blocked_at = extra - step;
+        match table {
+            Some(stop) if guard.pos.distance(&stop) < distance as u32 => Some(stop),
+            _ => Some(blocked_at),
+        }
+    }
+
+    /// Whether a guard in state `from` loops once `extra` is an obstacle,
+    /// jumping straight from turn to turn
+    pub fn loops_with(&self, from: Guard, extra: Point) -> bool {
+        let mut seen: HashSet<Guard> = HashSet::new();
+        let mut guard = from;
+        while let Some(pos) = self.next_stop(guard, extra) {
+            guard = Guard {
+                pos,
+                heading: guard.heading.turn_right(),
+            };
+            if !seen.insert(guard) {
+                return true;
             }
         }
+        false
     }
-    let mut total_positions_visited = 1;
-    while let Some(position_visited) = character.take_step(world) {
-        total_positions_visited += position_visited;
+
+    /// Every cell where one new obstacle would trap the guard in a loop. Only
+    /// cells on the original path can change anything, and the guard's walk
+    /// up to that cell is unchanged, so each check starts right in front of it.
+    pub fn loop_obstacles(&self, walk: &Walk) -> Vec<Point> {
+        walk.visited
+            .iter()
+            .zip(walk.entered_from.iter())
+            .filter_map(|(pos, from)| Some((*pos, (*from)?)))
+            .filter(|(pos, from)| self.loops_with(*from, *pos))
+            .map(|(pos, _)| pos)
+            .collect()
+    }
+
+    pub fn render(&self, walk: &Walk, obstacles: &[Point]) -> ByteGrid {
+        let mut world = self.grid.clone();
+        for p in walk.visited.iter() {
+            world[*p] = b'X';
+        }
+        for p in obstacles {
+            world[*p] = b'O';
+        }
+        world[self.start.pos] = self.start.heading.to_arrow();
+        world
     }
-    total_positions_visited
 }
 
-pub fn part1(args: &Args) -> u64 {
-    let input = fs::read_to_string(&args.file).expect("I/O error");
-    let mut world = string_to_2d_array(input);
-    let res = run_simulation(&mut world);
-    print_2d_array(&world);
-    res
+pub fn part1(lab: &Lab) -> usize {
+    lab.walk().visited.len()
 }
 
-pub fn part2(args: &Args) -> u64 {
-    let input = fs::read_to_string(&args.file).expect("I/O error");
-    let world = string_to_2d_array(input);
-    let mut total_causes_loop = 0;
-    for y in 0..world.len() {
-        for x in 0..world[0].len() {
-            if world[y][x] == '.' {
-                let mut new_world = world.clone();
-                new_world[y][x] = 'O';
-                run_simulation(&mut new_world);
-                if new_world.iter().any(|l| l.iter().any(|c| *c == '5')) {
-                    // print_2d_array(&new_world);
-                    // println!();
-                    total_causes_loop += 1;
-                }
-            }
-        }
-    }
-    total_causes_loop
+pub fn part2(lab: &Lab) -> usize {
+    lab.loop_obstacles(&lab.walk()).len()
 }
 
 pub fn entrypoint(args: &Args) {
+    let input = fs::read_to_string(&args.file).expect("I/O error");
+    let lab = Lab::new(&input);
+    if args.show {
+        let walk = lab.walk();
+        let obstacles = if args.part2 {
+            lab.loop_obstacles(&walk)
+        } else {
+            vec![]
+        };
+        println!("{}", lab.render(&walk, &obstacles));
+    }
     if args.part2 {
-        let res = part2(args);
+        let res = part2(&lab);
         println!("{}", res);
     } else {
-        let res = part1(args);
+        let res = part1(&lab);
         println!("{}", res);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day6() {
+        let input = fs::read_to_string("./inputs/day6/sample.txt").unwrap();
+        let lab = Lab::new(&input);
+        let walk = lab.walk();
+        assert!(!walk.loops);
+        assert_eq!(walk.visited.len(), 41);
+        assert_eq!(walk.visited.len(), walk.entered_from.len());
+        let mut obstacles = lab.loop_obstacles(&walk);
+        obstacles.sort();
+        assert_eq!(
+            obstacles,
+            vec![
+                Point::new(6, 3),
+                Point::new(7, 6),
+                Point::new(7, 7),
+                Point::new(8, 1),
+                Point::new(8, 3),
+                Point::new(9, 7)
+            ]
+        );
+
+        // boxed in on every side, so it just spins on the spot
+        let lab = Lab::new("...\n.#.\n#^#\n.#.");
+        assert!(lab.walk().loops);
+    }
+}
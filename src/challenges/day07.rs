@@ -1,5 +1,6 @@
 use advent24::parse_number_list;
 use clap;
+use itertools::Itertools;
 use std::{fs, str};
 
 #[derive(clap::Args, Debug)]
@@ -9,88 +10,275 @@ pub struct Args {
     /// Find "gear ratios"
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Operators to try, overriding the ones implied by --part2
+    #[arg(long, value_enum, value_delimiter = ',')]
+    ops: Vec<Operator>,
+
+    /// Print the expression found for each solvable equation
+    #[clap(long, action)]
+    show: bool,
 }
 
-pub fn part1(args: &Args) -> i64 {
-    let input = fs::read_to_string(&args.file).expect("I/O error");
-    let mut grand_total = 0;
-    for line in input.lines() {
-        let mut part_iter = line.split(":");
-        let correct_total: i64 = part_iter.next().unwrap().parse().unwrap();
-        let parts: Vec<i64> = parse_number_list(part_iter.next().unwrap());
-        for i in 0..i64::pow(2, (parts.len() - 1) as u32) {
-            let mut total = parts[0];
-            for (j, part) in parts[1..].iter().enumerate() {
-                if i & 1 << j > 0 {
-                    total += part;
-                } else {
-                    total *= part;
-                }
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    Add,
+    Mul,
+    Concat,
+    Sub,
+    Xor,
+}
+
+/// What the left hand side of `lhs op rhs == target` has to be
+#[derive(Debug, PartialEq, Eq)]
+pub enum Inverse {
+    Impossible,
+    Exactly(i64),
+    /// Every left hand side works, e.g. `lhs * 0 == 0`
+    Any,
+}
+
+fn digits_pow(n: i64) -> Option<i64> {
+    10i64.checked_pow(n.checked_ilog10().unwrap_or(0) + 1)
+}
+
+impl Operator {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Mul => "*",
+            Operator::Concat => "||",
+            Operator::Sub => "-",
+            Operator::Xor => "^",
+        }
+    }
+
+    /// `None` on overflow, or when concatenating a negative number
+    pub fn apply(self, lhs: i64, rhs: i64) -> Option<i64> {
+        match self {
+            Operator::Add => lhs.checked_add(rhs),
+            Operator::Mul => lhs.checked_mul(rhs),
+            Operator::Concat if lhs >= 0 && rhs >= 0 => {
+                lhs.checked_mul(digits_pow(rhs)?)?.checked_add(rhs)
             }
-            if total == correct_total {
-                grand_total += total;
-                break;
+            Operator::Concat => None,
+            Operator::Sub => lhs.checked_sub(rhs),
+            Operator::Xor => Some(lhs ^ rhs),
+        }
+    }
+
+    pub fn invert(self, target: i64, rhs: i64) -> Inverse {
+        let exactly = |lhs: Option<i64>| lhs.map_or(Inverse::Impossible, Inverse::Exactly);
+        match self {
+            Operator::Add => exactly(target.checked_sub(rhs)),
+            Operator::Mul if rhs == 0 && target == 0 => Inverse::Any,
+            Operator::Mul if rhs == 0 || target % rhs != 0 => Inverse::Impossible,
+            Operator::Mul => exactly(Some(target / rhs)),
+            Operator::Concat => match digits_pow(rhs) {
+                // the target has to end with the digits of rhs
+                Some(pow) if target >= 0 && rhs >= 0 && target % pow == rhs => {
+                    exactly(Some(target / pow))
+                }
+                _ => Inverse::Impossible,
+            },
+            Operator::Sub => exactly(target.checked_add(rhs)),
+            Operator::Xor => exactly(Some(target ^ rhs)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equation {
+    pub target: i64,
+    pub nums: Vec<i64>,
+}
+
+impl Equation {
+    /// Operators (applied left to right) that turn `nums` into `target`.
+    ///
+    /// Works backwards from the target by undoing the last operation, so
+    /// mismatched divisors and suffixes are pruned right away instead of
+    /// enumerating every combination.
+    pub fn solve(&self, ops: &[Operator]) -> Option<Vec<Operator>> {
+        // with only growing operators and positive numbers, every partial
+        // result is positive and no bigger than the target
+        let monotonic = self.nums.iter().all(|n| *n > 0)
+            && ops
+                .iter()
+                .all(|op| matches!(op, Operator::Add | Operator::Mul | Operator::Concat));
+        let mut found: Vec<Operator> = vec![];
+        if search(self.target, &self.nums, ops, monotonic, &mut found) {
+            Some(found)
+        } else {
+            None
+        }
+    }
+
+    pub fn expression(&self, ops: &[Operator]) -> String {
+        let mut expr = format!("{}", self.nums[0]);
+        for (op, n) in ops.iter().zip(self.nums[1..].iter()) {
+            expr += &format!(" {} {}", op.symbol(), n);
+        }
+        format!("{} = {}", self.target, expr)
+    }
+}
+
+fn search(
+    target: i64,
+    nums: &[i64],
+    ops: &[Operator],
+    monotonic: bool,
+    found: &mut Vec<Operator>,
+) -> bool {
+    let Some((last, rest)) = nums.split_last() else {
+        return false;
+    };
+    if rest.is_empty() {
+        return *last == target;
+    }
+    if monotonic && target < 1 {
+        return false;
+    }
+    for op in ops {
+        let matched = match op.invert(target, *last) {
+            Inverse::Impossible => false,
+            Inverse::Exactly(lhs) => search(lhs, rest, ops, monotonic, found),
+            Inverse::Any => evaluate_any(rest[0], &rest[1..], ops, found),
+        };
+        if matched {
+            found.push(*op);
+            return true;
+        }
+    }
+    false
+}
+
+/// Find any operators that evaluate `nums` without overflowing
+fn evaluate_any(acc: i64, nums: &[i64], ops: &[Operator], found: &mut Vec<Operator>) -> bool {
+    let Some((first, rest)) = nums.split_first() else {
+        return true;
+    };
+    for op in ops {
+        if let Some(next) = op.apply(acc, *first) {
+            found.push(*op);
+            if evaluate_any(next, rest, ops, found) {
+                return true;
             }
+            found.pop();
         }
     }
-    grand_total
+    false
 }
 
-pub fn part2(args: &Args) -> i64 {
-    let input = fs::read_to_string(&args.file).expect("I/O error");
+pub fn parse(input: &str) -> Vec<Equation> {
+    input
+        .lines()
+        .filter_map(|line| {
+            let (target, nums) = line.split_once(':')?;
+            Some(Equation {
+                target: target.trim().parse().ok()?,
+                nums: parse_number_list(nums),
+            })
+        })
+        .filter(|eq| !eq.nums.is_empty())
+        .collect()
+}
+
+pub fn calibration(equations: &[Equation], ops: &[Operator], show: bool) -> i64 {
     let mut grand_total = 0;
-    for line in input.lines() {
-        let mut part_iter = line.split(":");
-        let correct_total: i64 = part_iter.next().unwrap().parse().unwrap();
-        let parts: Vec<i64> = parse_number_list(part_iter.next().unwrap());
-        for i in 0..i64::pow(3, (parts.len() - 1) as u32) {
-            let mut remaining = i;
-            let mut total = parts[0];
-            for (j, part) in parts[1..].iter().enumerate() {
-                let place = i64::pow(3, (parts.len() - j - 2) as u32);
-                let digit = (remaining / place) as i64;
-                remaining = (remaining % place) as i64;
-                total = match digit {
-                    0 => total + part,
-                    1 => total * part,
-                    2 => total * (i64::pow(10, (*part as f64).log10() as u32 + 1)) + part,
-                    _ => unreachable!(),
-                }
-            }
-            if total == correct_total {
-                grand_total += total;
-                break;
+    for equation in equations {
+        if let Some(found) = equation.solve(ops) {
+            if show {
+                println!("{}", equation.expression(&found));
             }
+            grand_total += equation.target;
         }
     }
     grand_total
 }
 
+pub fn part1(equations: &[Equation]) -> i64 {
+    calibration(equations, &[Operator::Add, Operator::Mul], false)
+}
+
+pub fn part2(equations: &[Equation]) -> i64 {
+    calibration(
+        equations,
+        &[Operator::Add, Operator::Mul, Operator::Concat],
+        false,
+    )
+}
+
 pub fn entrypoint(args: &Args) {
-    if args.part2 {
-        let res = part2(args);
+    let input = fs::read_to_string(&args.file).expect("I/O error");
+    let equations = parse(&input);
+    if !args.ops.is_empty() || args.show {
+        let ops = if !args.ops.is_empty() {
+            args.ops.iter().copied().unique().collect()
+        } else if args.part2 {
+            vec![Operator::Add, Operator::Mul, Operator::Concat]
+        } else {
+            vec![Operator::Add, Operator::Mul]
+        };
+        let res = calibration(&equations, &ops, args.show);
+        println!("{}", res);
+    } else if args.part2 {
+        let res = part2(&equations);
         println!("{}", res);
     } else {
-        let res = part1(args);
+        let res = part1(&equations);
         println!("{}", res);
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use test::Bencher;
-
-//     #[test]
-//     fn test_u8_grid() {
-//         let mut grid = ByteGrid::new(
-//             "190: 10 19\n\
-//              3267: 81 40 27\n\
-//              83: 17 5\n\
-//              156: 15 6\n\
-//              7290: 6 8 6 15\n\
-//              161011: 16 10 13\n\
-//              192: 17 8 14\n\
-//              21037: 9 7 18 13\n\
-//              292: 11 6 16 20",
-//         );
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_GRID: &str = "\
+190: 10 19
+3267: 81 40 27
+83: 17 5
+156: 15 6
+7290: 6 8 6 15
+161011: 16 10 13
+192: 17 8 14
+21037: 9 7 18 13
+292: 11 6 16 20";
+
+    #[test]
+    fn test_day7() {
+        let equations = parse(TEST_GRID);
+        assert_eq!(part1(&equations), 3749);
+        assert_eq!(part2(&equations), 11387);
+
+        let ops = [Operator::Add, Operator::Mul, Operator::Concat];
+        let found = equations[4].solve(&ops).unwrap();
+        assert_eq!(equations[4].expression(&found), "7290 = 6 * 8 || 6 * 15");
+        assert_eq!(equations[2].solve(&ops), None);
+
+        // concatenation can't be faked with log10 rounding
+        let eq = Equation {
+            target: 1000,
+            nums: vec![1, 0, 0, 0],
+        };
+        assert_eq!(eq.solve(&ops).map(|f| f.len()), Some(3));
+        assert_eq!(Operator::Concat.apply(12, 100), Some(12100));
+        assert_eq!(Operator::Concat.invert(12100, 100), Inverse::Exactly(12));
+        assert_eq!(Operator::Concat.invert(12100, 10), Inverse::Impossible);
+
+        let eq = Equation {
+            target: 3,
+            nums: vec![10, 5, 2],
+        };
+        assert_eq!(eq.solve(&ops), None);
+        let found = eq.solve(&[Operator::Sub, Operator::Xor]).unwrap();
+        assert_eq!(eq.expression(&found), "3 = 10 - 5 - 2");
+        let eq = Equation {
+            target: 0,
+            nums: vec![7, 3, 0],
+        };
+        let found = eq.solve(&[Operator::Sub, Operator::Mul]).unwrap();
+        assert_eq!(eq.expression(&found), "0 = 7 - 3 * 0");
+    }
+}
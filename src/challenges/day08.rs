@@ -1,7 +1,13 @@
-use advent24::bytegrid::{ByteGrid, ORIGIN};
+use advent24::{
+    bytegrid::{ByteGrid, Point},
+    linalg::gcd,
+};
 use clap;
 use itertools::{self, Itertools};
-use std::{fs, str};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, str,
+};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -10,42 +16,187 @@ pub struct Args {
     /// Find "gear ratios"
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Count antinodes under this rule only; without it both parts are
+    /// printed
+    #[arg(long, value_enum)]
+    rule: Option<Rule>,
+
+    /// Distance ratio for the harmonic rule
+    #[arg(long, default_value_t = 2)]
+    ratio: i32,
+
+    /// Print the antinodes of each frequency on its own map
+    #[clap(long, action)]
+    overlays: bool,
+
+    /// Print cells that are antinodes of more than one frequency
+    #[clap(long, action)]
+    overlaps: bool,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    Harmonic,
+    Line,
+    Lattice,
+}
+
+/// Which points a pair of same-frequency antennas `a` and `b` resonate at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resonance {
+    /// Points on the line outside the pair that are `ratio` times as far
+    /// from one antenna as the other (part 1 uses 2)
+    Harmonic(i32),
+    /// Every multiple of `b - a` along the line, including the antennas
+    Line,
+    /// Every grid point exactly on the line, stepping by `b - a` divided by
+    /// the gcd of its coordinates
+    Lattice,
 }
 
-pub fn solver(input: &str, skip: usize, take: usize) -> usize {
-    let mut world = ByteGrid::new(input);
-    let mut bytes_to_points = world.bytes_to_points();
-    bytes_to_points.remove(&b'.');
-    for point_vec in bytes_to_points.values() {
-        for points in point_vec.iter().combinations(2) {
-            let (mut smaller, mut larger) = (*points[0], *points[1]);
-            if smaller > larger {
-                (smaller, larger) = (larger, smaller);
+impl Resonance {
+    pub fn new(rule: Rule, ratio: i32) -> Self {
+        match rule {
+            Rule::Harmonic => Resonance::Harmonic(ratio),
+            Rule::Line => Resonance::Line,
+            Rule::Lattice => Resonance::Lattice,
+        }
+    }
+
+    fn antinodes(self, grid: &ByteGrid, a: Point, b: Point, found: &mut BTreeSet<Point>) {
+        let diff = b - a;
+        let mut walk = |from: Point, step: Point| {
+            let mut p = from;
+            while grid.is_valid_point(p) {
+                found.insert(p);
+                p = p + step;
+            }
+        };
+        match self {
+            Resonance::Harmonic(ratio) => {
+                // |p - a| == ratio * |p - b| beyond b is a + diff * ratio / (ratio - 1)
+                let denom = ratio - 1;
+                if denom <= 0 || diff.row % denom != 0 || diff.col % denom != 0 {
+                    return;
+                }
+                let offset = Point::new(diff.row / denom, diff.col / denom);
+                for p in [b + offset, a - offset] {
+                    if grid.is_valid_point(p) {
+                        found.insert(p);
+                    }
+                }
             }
-            let distance = larger - smaller;
-            for antinode in world
-                .iter_mut_towards(smaller, ORIGIN - distance)
-                .skip(skip)
-                .take(take)
-            {
-                *antinode = b'#';
+            Resonance::Line => {
+                walk(a, diff);
+                walk(a, Point::default() - diff);
             }
-            for antinode in world
-                .iter_mut_towards(larger, distance)
-                .skip(skip)
-                .take(take)
-            {
-                *antinode = b'#';
+            Resonance::Lattice => {
+                let g = gcd(diff.row as i128, diff.col as i128) as i32;
+                let step = Point::new(diff.row / g, diff.col / g);
+                walk(a, step);
+                walk(a, Point::default() - step);
             }
         }
     }
-    world.count(b'#')
+}
+
+pub struct Antennas {
+    pub grid: ByteGrid,
+    pub frequencies: BTreeMap<u8, Vec<Point>>,
+}
+
+impl Antennas {
+    pub fn new(input: &str) -> Self {
+        let grid = ByteGrid::new(input);
+        let frequencies = grid
+            .bytes_to_points()
+            .into_iter()
+            .filter(|(b, _)| b.is_ascii_alphanumeric())
+            .collect();
+        Antennas { grid, frequencies }
+    }
+
+    pub fn antinodes(&self, frequency: u8, resonance: Resonance) -> BTreeSet<Point> {
+        let mut found: BTreeSet<Point> = BTreeSet::new();
+        let Some(points) = self.frequencies.get(&frequency) else {
+            return found;
+        };
+        for pair in points.iter().combinations(2) {
+            resonance.antinodes(&self.grid, *pair[0], *pair[1], &mut found);
+        }
+        found
+    }
+
+    pub fn antinodes_by_frequency(&self, resonance: Resonance) -> BTreeMap<u8, BTreeSet<Point>> {
+        self.frequencies
+            .keys()
+            .map(|f| (*f, self.antinodes(*f, resonance)))
+            .collect()
+    }
+
+    /// Unique antinode positions across every frequency
+    pub fn count(&self, resonance: Resonance) -> usize {
+        self.antinodes_by_frequency(resonance)
+            .into_values()
+            .flatten()
+            .collect::<BTreeSet<Point>>()
+            .len()
+    }
+
+    /// This frequency's antennas and antinodes, with other antennas hidden
+    pub fn overlay(&self, frequency: u8, antinodes: &BTreeSet<Point>) -> ByteGrid {
+        let mut world = ByteGrid::new_empty(b'.', self.grid.width, self.grid.height);
+        for p in antinodes {
+            world[*p] = b'#';
+        }
+        for p in self.frequencies.get(&frequency).into_iter().flatten() {
+            world[*p] = frequency;
+        }
+        world
+    }
+}
+
+/// Cells that are antinodes for more than one frequency, with those frequencies
+pub fn overlaps(by_frequency: &BTreeMap<u8, BTreeSet<Point>>) -> Vec<(Point, Vec<u8>)> {
+    let mut sources: BTreeMap<Point, Vec<u8>> = BTreeMap::new();
+    for (frequency, antinodes) in by_frequency {
+        for p in antinodes {
+            sources.entry(*p).or_default().push(*frequency);
+        }
+    }
+    sources
+        .into_iter()
+        .filter(|(_, frequencies)| frequencies.len() > 1)
+        .collect()
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    println!("part1: {}", solver(&input, 1, 1));
-    println!("part2: {}", solver(&input, 0, 1000));
+    let antennas = Antennas::new(&input);
+    if let Some(rule) = args.rule {
+        let resonance = Resonance::new(rule, args.ratio);
+        let by_frequency = antennas.antinodes_by_frequency(resonance);
+        if args.overlays {
+            for (frequency, antinodes) in by_frequency.iter() {
+                println!(
+                    "{}:\n{}",
+                    *frequency as char,
+                    antennas.overlay(*frequency, antinodes)
+                );
+            }
+        }
+        if args.overlaps {
+            for (p, frequencies) in overlaps(&by_frequency) {
+                let frequencies: String = frequencies.iter().map(|f| *f as char).collect();
+                println!("({}, {}): {}", p.row, p.col, frequencies);
+            }
+        }
+        println!("{}", antennas.count(resonance));
+    } else {
+        println!("part1: {}", antennas.count(Resonance::Harmonic(2)));
+        println!("part2: {}", antennas.count(Resonance::Lattice));
+    }
 }
 
 #[cfg(test)]
@@ -68,7 +219,24 @@ mod tests {
 
     #[test]
     fn test_day8() {
-        assert_eq!(solver(TEST_GRID, 1, 1), 14);
-        assert_eq!(solver(TEST_GRID, 0, 100), 34);
+        let antennas = Antennas::new(TEST_GRID);
+        assert_eq!(antennas.count(Resonance::Harmonic(2)), 14);
+        assert_eq!(antennas.count(Resonance::Line), 34);
+        assert_eq!(antennas.count(Resonance::Lattice), 34);
+
+        let by_frequency = antennas.antinodes_by_frequency(Resonance::Harmonic(2));
+        assert_eq!(by_frequency[&b'A'].len(), 5);
+        assert_eq!(
+            overlaps(&by_frequency),
+            vec![(Point::new(1, 3), vec![b'0', b'A'])]
+        );
+        assert_eq!(antennas.overlay(b'A', &by_frequency[&b'A']).count(b'#'), 5);
+
+        // the raw difference skips points that lie exactly between the pair
+        let antennas = Antennas::new("a....\n.....\n..a..\n.....\n.....");
+        assert_eq!(antennas.count(Resonance::Line), 3);
+        assert_eq!(antennas.count(Resonance::Lattice), 5);
+        // a point three times as far from one as the other, one step beyond
+        assert_eq!(antennas.count(Resonance::Harmonic(3)), 1);
     }
 }
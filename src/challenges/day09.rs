@@ -1,9 +1,7 @@
 use clap;
-use itertools::PeekingNext;
 use std::{
-    cmp::Reverse,
-    collections::{BinaryHeap, HashSet},
-    fs,
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
 };
 
 #[derive(clap::Args, Debug)]
@@ -13,173 +11,392 @@ pub struct Args {
     /// Find "gear ratios"
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Compaction strategy, overriding the one implied by --part2
+    #[arg(long, value_enum)]
+    strategy: Option<Strategy>,
+
+    /// Print the disk layout after every move made by --strategy
+    #[clap(long, action)]
+    show_steps: bool,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct File {
+    pub id: usize,
+    pub pos: usize,
+    pub size: usize,
+}
+
+/// An entry of the disk map that isn't a length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiskError {
+    pub index: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ParseDiskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bad length {:?} at entry {}", self.token, self.index)
+    }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum Block {
-    File { id: usize },
-    Free,
+/// The disk as runs of file blocks, so memory only grows with the number of
+/// entries in the disk map and not with their lengths
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskMap {
+    /// `pos -> (size, id)` for every run of blocks of one file, everything
+    /// else is free
+    spans: BTreeMap<usize, (usize, usize)>,
+    /// Total number of blocks, free ones included
+    pub len: usize,
 }
 
-pub fn parse_puzzle(input: &str) -> Vec<Block> {
-    let mut output: Vec<Block> = vec![];
-    let mut id = 0;
-    for (i, c) in input.chars().enumerate() {
-        if !c.is_ascii_digit() {
-            println!("warn: bad char: {}", c);
-            continue;
+impl DiskMap {
+    /// Parse the dense format, where digits alternate between file and free
+    /// space lengths. Lengths above 9 can be given by separating them with
+    /// commas instead, e.g. `12,0,3`.
+    pub fn parse(input: &str) -> Result<Self, ParseDiskError> {
+        let input = input.trim();
+        let bad = |index: usize, token: &str| ParseDiskError {
+            index,
+            token: token.to_string(),
+        };
+        let lengths: Vec<usize> = if input.contains(',') {
+            input
+                .split(',')
+                .map(str::trim)
+                .enumerate()
+                .map(|(i, token)| token.parse().map_err(|_| bad(i, token)))
+                .collect::<Result<_, _>>()?
+        } else {
+            input
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    c.to_digit(10)
+                        .map(|d| d as usize)
+                        .ok_or_else(|| bad(i, &c.to_string()))
+                })
+                .collect::<Result<_, _>>()?
+        };
+        let mut disk = DiskMap::default();
+        for (i, size) in lengths.into_iter().enumerate() {
+            if i % 2 == 0 {
+                if size == 0 {
+                    println!("warn: 0 len file @ input[{}]", i);
+                } else {
+                    disk.spans.insert(disk.len, (size, i / 2));
+                }
+            }
+            disk.len += size;
         }
-        if c == '0' && i % 2 == 0 {
-            println!("warn: 0 len file @ input[{}]", i);
+        Ok(disk)
+    }
+
+    /// Every file on the disk by id, assuming files are contiguous
+    pub fn files(&self) -> Vec<File> {
+        let mut files: Vec<File> = vec![];
+        for (pos, (size, id)) in self.spans.iter() {
+            if *id >= files.len() {
+                files.resize(id + 1, File::default());
+            }
+            if files[*id].size == 0 {
+                files[*id] = File {
+                    id: *id,
+                    pos: *pos,
+                    size: 0,
+                };
+            }
+            files[*id].size += size;
         }
-        for _k in 0..c.to_digit(10).unwrap() {
-            if i % 2 == 0 {
-                output.push(Block::File { id });
-            } else {
-                output.push(Block::Free)
+        files.retain(|f| f.size > 0);
+        files
+    }
+
+    /// The first run of free blocks at or after `pos`, as `(pos, size)`
+    fn first_gap_from(&self, pos: usize) -> Option<(usize, usize)> {
+        let mut at = pos;
+        if let Some((start, (size, _))) = self.spans.range(..=pos).next_back() {
+            at = at.max(start + size);
+        }
+        for (start, (size, _)) in self.spans.range(at..) {
+            if *start > at {
+                return Some((at, start - at));
             }
+            at = start + size;
+        }
+        (at < self.len).then_some((at, self.len - at))
+    }
+
+    /// Every run of free blocks as `(pos, size)`
+    pub fn gaps(&self) -> Vec<(usize, usize)> {
+        let mut gaps: Vec<(usize, usize)> = vec![];
+        let mut at = 0;
+        while let Some((pos, size)) = self.first_gap_from(at) {
+            gaps.push((pos, size));
+            at = pos + size;
         }
-        if i % 2 == 0 {
-            id += 1;
+        gaps
+    }
+
+    /// Add a run of blocks, joining it onto the run before it if that one
+    /// belongs to the same file
+    fn insert_span(&mut self, pos: usize, size: usize, id: usize) {
+        if let Some((prev_pos, (prev_size, prev_id))) = self.spans.range_mut(..pos).next_back() {
+            if *prev_id == id && prev_pos + *prev_size == pos {
+                *prev_size += size;
+                return;
+            }
         }
+        self.spans.insert(pos, (size, id));
+    }
+
+    pub fn move_file(&mut self, file: &File, new_pos: usize) {
+        let removed = self.spans.remove(&file.pos);
+        assert_eq!(
+            removed,
+            Some((file.size, file.id)),
+            "file {} isn't contiguous",
+            file.id
+        );
+        self.insert_span(new_pos, file.size, file.id);
+    }
+
+    pub fn checksum(&self) -> usize {
+        // sum of id * i over pos..pos + size
+        self.spans
+            .iter()
+            .map(|(pos, (size, id))| id * (pos * size + size * (size - 1) / 2))
+            .sum()
     }
-    output
 }
 
-pub fn score_fs(fs: &[Block]) -> usize {
-    let mut sum = 0;
-    for (i, block) in fs.iter().enumerate() {
-        if let Block::File { id } = block {
-            sum += id * i;
+/// Renders the layout the way the puzzle does, e.g. `00...111...2`. Ids above
+/// 9 only show their last digit.
+impl fmt::Display for DiskMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut at = 0;
+        for (pos, (size, id)) in self.spans.iter() {
+            write!(f, "{}", ".".repeat(pos - at))?;
+            write!(f, "{}", (id % 10).to_string().repeat(*size))?;
+            at = pos + size;
         }
+        write!(f, "{}", ".".repeat(self.len - at))
     }
-    sum
 }
 
-pub fn part1(input: &str) -> usize {
-    let fs = parse_puzzle(input);
-    let mut new_fs: Vec<Block> = vec![];
-    let fwd_iter = fs.iter();
-    let mut rev_iter = fs.iter().rev();
-    let mut seen_ids = HashSet::<usize>::new();
-    let mut total_i = 0;
-    for block in fwd_iter {
-        match *block {
-            Block::File { id } => {
-                new_fs.push(*block);
-                seen_ids.insert(id);
-                total_i += 1;
-            }
-            Block::Free => {
-                while let Some(Block::Free) = rev_iter.peeking_next(|x| **x == Block::Free) {
-                    total_i += 1;
-                }
-                if total_i >= fs.len() {
-                    break;
-                }
-                if let Block::File { id } = *rev_iter.next().unwrap() {
-                    new_fs.push(Block::File { id });
-                    total_i += 2;
-                }
-            }
+pub trait Compaction {
+    /// Make a single move, returning false once there is nothing left to do
+    fn step(&mut self, disk: &mut DiskMap) -> bool;
+
+    fn compact(&mut self, disk: &mut DiskMap) {
+        while self.step(disk) {}
+    }
+}
+
+/// Moves blocks from the end of the disk into the leftmost free block,
+/// fragmenting files as it goes. Each step moves a single block, but
+/// `compact` moves as many as fit at once.
+#[derive(Debug, Default)]
+pub struct BlockWise {
+    /// Everything before this is known to be full
+    front: usize,
+}
+
+impl BlockWise {
+    fn advance(&mut self, disk: &mut DiskMap, max_blocks: usize) -> bool {
+        let Some((gap_pos, gap_size)) = disk.first_gap_from(self.front) else {
+            return false;
+        };
+        self.front = gap_pos;
+        let Some((&last_pos, &(last_size, id))) = disk.spans.last_key_value() else {
+            return false;
+        };
+        if gap_pos >= last_pos {
+            return false;
         }
-        if total_i >= fs.len() {
-            break;
+        let moved = gap_size.min(last_size).min(max_blocks);
+        if moved == last_size {
+            disk.spans.remove(&last_pos);
+        } else {
+            disk.spans.insert(last_pos, (last_size - moved, id));
         }
+        disk.insert_span(gap_pos, moved, id);
+        true
     }
-    score_fs(&new_fs)
 }
 
-#[derive(Debug, Default, Copy, Clone)]
-pub struct File {
-    id: usize,
-    pos: usize,
-    size: usize,
+impl Compaction for BlockWise {
+    fn step(&mut self, disk: &mut DiskMap) -> bool {
+        self.advance(disk, 1)
+    }
+
+    fn compact(&mut self, disk: &mut DiskMap) {
+        while self.advance(disk, usize::MAX) {}
+    }
 }
 
+/// Free space indexed by gap size, so finding a gap big enough for a file
+/// doesn't require scanning the disk
 #[derive(Debug, Default)]
-struct Defragmenter {
-    pub gap_to_pos: [BinaryHeap<Reverse<usize>>; 9],
-    pub files: Vec<File>,
-}
-
-impl Defragmenter {
-    pub fn new(input: &str) -> Self {
-        let mut s = Defragmenter::default();
-        let mut pos = 0;
-        let mut id = 0;
-        for (i, c) in input.chars().enumerate() {
-            if !c.is_ascii_digit() {
-                println!("warn: bad char: {}", c);
-                continue;
-            }
-            if c == '0' && i % 2 == 0 {
-                println!("warn: 0 len file @ input[{}]", i);
-            }
-            let size = c.to_digit(10).unwrap() as usize;
-            if i % 2 == 0 {
-                s.files.push(File { id, pos, size });
-                id += 1;
-            } else if size > 0 {
-                s.gap_to_pos[size - 1].push(Reverse(pos));
+struct GapIndex {
+    size_to_pos: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl GapIndex {
+    fn new(disk: &DiskMap) -> Self {
+        let mut index = GapIndex::default();
+        for (pos, size) in disk.gaps() {
+            index.insert(pos, size);
+        }
+        index
+    }
+
+    fn insert(&mut self, pos: usize, size: usize) {
+        if size > 0 {
+            self.size_to_pos.entry(size).or_default().insert(pos);
+        }
+    }
+
+    fn remove(&mut self, pos: usize, size: usize) {
+        if let Some(positions) = self.size_to_pos.get_mut(&size) {
+            positions.remove(&pos);
+            if positions.is_empty() {
+                self.size_to_pos.remove(&size);
             }
-            pos += size;
-        }
-        s
-    }
-
-    pub fn defragment(&mut self) {
-        for file in self.files.iter_mut().rev() {
-            let mut size_for_smallest_pos = usize::MAX;
-            let mut smallest_pos = usize::MAX;
-            for size in file.size..10 {
-                match self.gap_to_pos[size - 1].peek() {
-                    Some(Reverse(pos)) if *pos < smallest_pos => {
-                        smallest_pos = *pos;
-                        size_for_smallest_pos = size;
-                    }
-                    _ => (),
-                }
+        }
+    }
+
+    /// The gap `file` moves into under `fit`, as `(pos, size)`. Size classes
+    /// are visited smallest first without collecting them.
+    fn choose(&self, file: &File, fit: Fit) -> Option<(usize, usize)> {
+        let mut classes = self.size_to_pos.range(file.size.max(1)..);
+        match fit {
+            Fit::First => classes
+                .filter_map(|(size, set)| set.first().map(|pos| (*pos, *size)))
+                .filter(|(pos, _)| *pos < file.pos)
+                .min(),
+            // the first size class with a gap left of the file is the snuggest
+            Fit::Best => classes.find_map(|(size, set)| {
+                set.first()
+                    .filter(|pos| **pos < file.pos)
+                    .map(|pos| (*pos, *size))
+            }),
+            Fit::Reverse => classes
+                .filter_map(|(size, set)| set.last().map(|pos| (*pos, *size)))
+                .filter(|(pos, _)| *pos > file.pos)
+                .max(),
+        }
+    }
+}
+
+/// Which gap a whole file moves into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    /// Leftmost gap that fits, highest file id first
+    First,
+    /// Smallest gap that fits (leftmost on ties), highest file id first
+    Best,
+    /// Rightmost gap that fits, lowest file id first, packing files against
+    /// the end of the disk instead of the start
+    Reverse,
+}
+
+/// Moves whole files, each at most once
+#[derive(Debug)]
+pub struct WholeFile {
+    fit: Fit,
+    state: Option<(Vec<File>, GapIndex)>,
+}
+
+impl WholeFile {
+    pub fn new(fit: Fit) -> Self {
+        WholeFile { fit, state: None }
+    }
+}
+
+impl Compaction for WholeFile {
+    fn step(&mut self, disk: &mut DiskMap) -> bool {
+        let fit = self.fit;
+        let (files, gaps) = self.state.get_or_insert_with(|| {
+            let mut files = disk.files();
+            if fit == Fit::Reverse {
+                files.reverse();
             }
-            if smallest_pos < file.pos {
-                // better position found
-                if let Some(Reverse(new_pos)) = self.gap_to_pos[size_for_smallest_pos - 1].pop() {
-                    if file.size < size_for_smallest_pos {
-                        let remaining_size = size_for_smallest_pos - file.size;
-                        let remaining_pos = new_pos + file.size;
-                        self.gap_to_pos[remaining_size - 1].push(Reverse(remaining_pos));
-                    }
-                    file.pos = new_pos;
-                }
+            (files, GapIndex::new(disk))
+        });
+        let Some(file) = files.pop() else {
+            return false;
+        };
+        if let Some((pos, size)) = gaps.choose(&file, fit) {
+            gaps.remove(pos, size);
+            let remaining = size - file.size;
+            if fit == Fit::Reverse {
+                gaps.insert(pos, remaining);
+                disk.move_file(&file, pos + remaining);
+            } else {
+                gaps.insert(pos + file.size, remaining);
+                disk.move_file(&file, pos);
             }
         }
+        true
     }
 }
 
-pub fn score_files(files: &Vec<File>) -> usize {
-    let mut score = 0;
-    for file in files {
-        for i in 0..file.size {
-            score += file.id * (file.pos + i)
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum Strategy {
+    BlockWise,
+    FirstFit,
+    BestFit,
+    Reverse,
+}
+
+impl Strategy {
+    pub fn compactor(self) -> Box<dyn Compaction> {
+        match self {
+            Strategy::BlockWise => Box::new(BlockWise::default()),
+            Strategy::FirstFit => Box::new(WholeFile::new(Fit::First)),
+            Strategy::BestFit => Box::new(WholeFile::new(Fit::Best)),
+            Strategy::Reverse => Box::new(WholeFile::new(Fit::Reverse)),
         }
     }
-    score
 }
 
-pub fn part2(input: &str) -> usize {
-    let mut defragmenter = Defragmenter::new(input);
-    defragmenter.defragment();
-    score_files(&defragmenter.files)
+pub fn solve(input: &str, strategy: Strategy, show_steps: bool) -> Result<usize, ParseDiskError> {
+    let mut disk = DiskMap::parse(input)?;
+    let mut compactor = strategy.compactor();
+    if show_steps {
+        println!("{}", disk);
+        while compactor.step(&mut disk) {
+            println!("{}", disk);
+        }
+    } else {
+        compactor.compact(&mut disk);
+    }
+    Ok(disk.checksum())
+}
+
+pub fn part1(input: &str) -> Result<usize, ParseDiskError> {
+    solve(input, Strategy::BlockWise, false)
+}
+
+pub fn part2(input: &str) -> Result<usize, ParseDiskError> {
+    solve(input, Strategy::FirstFit, false)
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    if args.part2 {
-        let res = part2(&input);
-        println!("{}", res);
+    let res = if let Some(strategy) = args.strategy {
+        solve(&input, strategy, args.show_steps)
+    } else if args.part2 {
+        part2(&input)
     } else {
-        let res = part1(&input);
-        println!("{}", res);
+        part1(&input)
+    };
+    match res {
+        Ok(res) => println!("{}", res),
+        Err(err) => println!("{}", err),
     }
 }
 
@@ -192,53 +409,113 @@ mod tests {
     #[test]
     fn test_day9_part1() {
         let p1 = part1(TEST_GRID);
-        assert_eq!(p1, 1928);
+        assert_eq!(p1, Ok(1928));
     }
 
     #[test]
     fn test_day9_part2() {
-        assert_eq!(part2(TEST_GRID), 2858);
+        assert_eq!(part2(TEST_GRID), Ok(2858));
 
         // correct scoring for ids > 9
-        assert_eq!(part2("1010101010101010101010"), 385);
+        assert_eq!(part2("1010101010101010101010"), Ok(385));
 
         // 0.1 -> 01. (simplest move)
-        assert_eq!(part2("111"), 1);
+        assert_eq!(part2("111"), Ok(1));
 
         // 0.........1 -> 01......... (simplest move, max space)
-        assert_eq!(part2("191"), 1);
+        assert_eq!(part2("191"), Ok(1));
 
         // 0..11 -> 011..
-        assert_eq!(part2("122"), 3);
+        assert_eq!(part2("122"), Ok(3));
 
         // 0.12 -> 021.
-        assert_eq!(part2("11101"), 4);
+        assert_eq!(part2("11101"), Ok(4));
 
         // 0...12 -> 021...
-        assert_eq!(part2("13101"), 4);
+        assert_eq!(part2("13101"), Ok(4));
 
         // 00..11..22 -> 002211....
-        assert_eq!(part2("22222"), 19);
+        assert_eq!(part2("22222"), Ok(19));
 
         // 00..1.2.3 -> 00321....
-        assert_eq!(part2("2211111"), 16);
+        assert_eq!(part2("2211111"), Ok(16));
 
         // 00..1.2.33.4 -> 00421...33..
-        assert_eq!(part2("221111211"), 69);
+        assert_eq!(part2("221111211"), Ok(69));
 
         // 0..1.22...333 -> 0221...333...
-        assert_eq!(part2("1211233"), 81);
+        assert_eq!(part2("1211233"), Ok(81));
 
         // 00.....11 -> 0011.....
-        assert_eq!(part2("252"), 5);
+        assert_eq!(part2("252"), Ok(5));
 
         // 0.1.2.3 -> 0312..
-        assert_eq!(part2("1111111"), 11);
+        assert_eq!(part2("1111111"), Ok(11));
 
         // 0...1223 -> 03221...
-        assert_eq!(part2("1310201"), 17);
+        assert_eq!(part2("1310201"), Ok(17));
+
+        assert_eq!(part2("354631466260"), Ok(1325));
+        assert_eq!(part2("171010402"), Ok(88));
+
+        // lengths above 9 in the comma separated format
+        assert_eq!(part2("1,12,1"), Ok(1));
+        assert_eq!(part2("10,2,1"), Ok(10));
+        assert_eq!(part2("1,20,15"), Ok(120));
+        assert_eq!(part2("2,3,3,3,1,3,3,1,2,1,4,1,4,1,3,1,4,0,2"), Ok(2858));
+        assert_eq!(
+            part2("2, 3, 3, 3, 1, 3, 3, 1, 2, 1, 4, 1, 4, 1, 3, 1, 4, 0, 2\n"),
+            Ok(2858)
+        );
+        assert_eq!(
+            part2("2,3,x,3"),
+            Err(ParseDiskError {
+                index: 2,
+                token: "x".to_string()
+            })
+        );
+        assert!(part2("2333a").is_err());
+
+        // lengths are never expanded into blocks
+        assert_eq!(part1("1,1000000000,1"), Ok(1));
+        assert_eq!(part2("1,1000000000,1"), Ok(1));
+        assert_eq!(part1("1000000000,1,1"), Ok(1000000000));
+        assert_eq!(
+            part2("1000000000,1000000000,1000000000"),
+            Ok(999999999 * 1000000000 / 2 + 1000000000 * 1000000000)
+        );
+    }
+
+    #[test]
+    fn test_day9_layout() {
+        let mut disk = DiskMap::parse(TEST_GRID).unwrap();
+        assert_eq!(
+            disk.to_string(),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+        WholeFile::new(Fit::First).compact(&mut disk);
+        assert_eq!(
+            disk.to_string(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+
+        let mut disk = DiskMap::parse("12345").unwrap();
+        let mut compactor = BlockWise::default();
+        compactor.step(&mut disk);
+        assert_eq!(disk.to_string(), "02.111....2222.");
+        compactor.compact(&mut disk);
+        assert_eq!(disk.to_string(), "022111222......");
 
-        assert_eq!(part2("354631466260"), 1325);
-        assert_eq!(part2("171010402"), 88);
+        // first fit fills the big gap, best fit prefers the snug one
+        let mut disk = DiskMap::parse("13111").unwrap();
+        WholeFile::new(Fit::First).compact(&mut disk);
+        assert_eq!(disk.to_string(), "021....");
+        let mut disk = DiskMap::parse("13111").unwrap();
+        WholeFile::new(Fit::Best).compact(&mut disk);
+        assert_eq!(disk.to_string(), "01...2.");
+        assert_eq!(disk.checksum(), 11);
+        let mut disk = DiskMap::parse("13111").unwrap();
+        WholeFile::new(Fit::Reverse).compact(&mut disk);
+        assert_eq!(disk.to_string(), "....102");
     }
 }
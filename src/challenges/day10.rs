@@ -1,6 +1,6 @@
 use advent24::bytegrid::{ByteGrid, Point};
 use clap;
-use std::{collections::VecDeque, fs};
+use std::{collections::BTreeSet, fs};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -9,54 +9,273 @@ pub struct Args {
     /// Find "gear ratios"
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Allow climbing by up to this much per step
+    #[arg(long, default_value_t = 1)]
+    max_step: u8,
+
+    /// Walk down from 9s to 0s instead of up
+    #[clap(long, action)]
+    descend: bool,
+
+    /// Allow diagonal moves
+    #[clap(long, action)]
+    diagonal: bool,
+
+    /// Print up to this many trails from each trailhead
+    #[arg(long)]
+    trails: Option<usize>,
+
+    /// Print the peaks reachable from each trailhead
+    #[clap(long, action)]
+    peaks: bool,
+
+    /// Print how many trails pass through each cell
+    #[clap(long, action)]
+    heat: bool,
+}
+
+/// Which moves a hiker may make between neighbouring cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepRule {
+    /// Largest height change per step (the smallest is always 1)
+    pub max_step: u8,
+    pub descend: bool,
+    pub diagonal: bool,
 }
 
-pub fn solve(input: &str) -> (i32, i32) {
-    let grid = ByteGrid::new(input);
-    let bytes_to_points = grid.bytes_to_points();
+impl Default for StepRule {
+    fn default() -> Self {
+        StepRule {
+            max_step: 1,
+            descend: false,
+            diagonal: false,
+        }
+    }
+}
 
-    let mut p1_score = 0;
-    let mut p2_score = 0;
+impl StepRule {
+    fn allows(&self, from: u8, to: u8) -> bool {
+        let change = if self.descend {
+            from as i32 - to as i32
+        } else {
+            to as i32 - from as i32
+        };
+        (1..=self.max_step as i32).contains(&change)
+    }
 
-    let mut queue: VecDeque<Point> = VecDeque::new();
-    let mut visited: Vec<i32> = vec![0; grid.data.len()];
+    fn trailhead(&self) -> u8 {
+        if self.descend {
+            b'9'
+        } else {
+            b'0'
+        }
+    }
 
-    for root in bytes_to_points[&b'0'].iter() {
-        queue.push_back(*root);
-        visited.fill(0);
-        visited[grid.point_to_idx(*root)] = 1;
+    fn peak(&self) -> u8 {
+        if self.descend {
+            b'0'
+        } else {
+            b'9'
+        }
+    }
+}
 
-        grid.bfs_all(*root, |p| {
-            let val = grid[p];
-            let mut adj = vec![];
-            let times_visited = visited[grid.point_to_idx(p)];
-            if val == b'9' {
-                p1_score += 1;
-                p2_score += times_visited;
+pub struct TopoMap {
+    pub grid: ByteGrid,
+    pub rule: StepRule,
+}
+
+impl TopoMap {
+    pub fn new(input: &str, rule: StepRule) -> Self {
+        TopoMap {
+            grid: ByteGrid::new(input),
+            rule,
+        }
+    }
+
+    pub fn trailheads(&self) -> Vec<Point> {
+        self.cells_at(self.rule.trailhead())
+    }
+
+    fn cells_at(&self, height: u8) -> Vec<Point> {
+        (0..self.grid.data.len())
+            .filter(|idx| self.grid[*idx] == height)
+            .map(|idx| self.grid.idx_to_point(idx))
+            .collect()
+    }
+
+    pub fn successors(&self, p: Point) -> Vec<Point> {
+        let from = self.grid[p];
+        if from == self.rule.peak() {
+            return vec![];
+        }
+        let neighbours = if self.rule.diagonal {
+            p.adjacencies().to_vec()
+        } else {
+            p.orthogonals().to_vec()
+        };
+        neighbours
+            .into_iter()
+            .filter(|n| {
+                self.grid
+                    .get_point(*n)
+                    .is_some_and(|to| to.is_ascii_digit() && self.rule.allows(from, *to))
+            })
+            .collect()
+    }
+
+    /// Every cell index, ordered so that each comes before the cells it
+    /// leads to
+    fn trail_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.grid.data.len())
+            .filter(|idx| self.grid[*idx].is_ascii_digit())
+            .collect();
+        order.sort_by_key(|idx| self.grid[*idx]);
+        if self.rule.descend {
+            order.reverse();
+        }
+        order
+    }
+
+    /// For every cell, how many trails lead from it to a peak
+    fn trails_to_peaks(&self) -> Vec<u64> {
+        let mut counts: Vec<u64> = vec![0; self.grid.data.len()];
+        for idx in self.trail_order().into_iter().rev() {
+            let p = self.grid.idx_to_point(idx);
+            counts[idx] = if self.grid[idx] == self.rule.peak() {
+                1
             } else {
-                for ortho in p
-                    .orthogonals()
-                    .into_iter()
-                    .filter(|p| grid.is_valid_point(*p) && grid[*p] == val + 1)
-                {
-                    if visited[grid.point_to_idx(ortho)] == 0 {
-                        adj.push(ortho);
-                    }
-                    visited[grid.point_to_idx(ortho)] += times_visited;
-                }
+                self.successors(p)
+                    .iter()
+                    .map(|n| counts[self.grid.point_to_idx(*n)])
+                    .sum()
+            };
+        }
+        counts
+    }
+
+    /// For every cell, how many trails from a trailhead reach it
+    fn trails_from_heads(&self) -> Vec<u64> {
+        let mut counts: Vec<u64> = vec![0; self.grid.data.len()];
+        for idx in self.trail_order() {
+            if self.grid[idx] == self.rule.trailhead() {
+                counts[idx] = 1;
+            }
+            for n in self.successors(self.grid.idx_to_point(idx)) {
+                counts[self.grid.point_to_idx(n)] += counts[idx];
+            }
+        }
+        counts
+    }
+
+    pub fn peaks_from(&self, head: Point) -> BTreeSet<Point> {
+        let mut peaks: BTreeSet<Point> = BTreeSet::new();
+        let mut seen: BTreeSet<Point> = BTreeSet::new();
+        let mut stack: Vec<Point> = vec![head];
+        while let Some(p) = stack.pop() {
+            if !seen.insert(p) {
+                continue;
+            }
+            if self.grid[p] == self.rule.peak() {
+                peaks.insert(p);
+            }
+            stack.extend(self.successors(p));
+        }
+        peaks
+    }
+
+    /// Up to `limit` complete trails starting at `head`
+    pub fn trails_from(&self, head: Point, limit: usize) -> Vec<Vec<Point>> {
+        let mut found: Vec<Vec<Point>> = vec![];
+        let mut trail: Vec<Point> = vec![head];
+        self.extend_trail(&mut trail, limit, &mut found);
+        found
+    }
+
+    fn extend_trail(&self, trail: &mut Vec<Point>, limit: usize, found: &mut Vec<Vec<Point>>) {
+        let last = *trail.last().unwrap();
+        if self.grid[last] == self.rule.peak() {
+            found.push(trail.clone());
+            return;
+        }
+        for next in self.successors(last) {
+            if found.len() >= limit {
+                return;
             }
-            Some(adj)
-        });
+            trail.push(next);
+            self.extend_trail(trail, limit, found);
+            trail.pop();
+        }
     }
 
-    (p1_score, p2_score)
+    /// Number of distinct peaks reachable from each trailhead, summed
+    pub fn score(&self) -> usize {
+        self.trailheads()
+            .into_iter()
+            .map(|head| self.peaks_from(head).len())
+            .sum()
+    }
+
+    /// Number of distinct trails from any trailhead to any peak
+    pub fn rating(&self) -> u64 {
+        let counts = self.trails_to_peaks();
+        self.trailheads()
+            .into_iter()
+            .map(|head| counts[self.grid.point_to_idx(head)])
+            .sum()
+    }
+
+    /// For every cell, how many complete trails pass through it
+    pub fn heat_map(&self) -> Vec<u64> {
+        self.trails_from_heads()
+            .into_iter()
+            .zip(self.trails_to_peaks())
+            .map(|(to_here, from_here)| to_here * from_here)
+            .collect()
+    }
+}
+
+fn format_trail(trail: &[Point]) -> String {
+    let cells: Vec<String> = trail
+        .iter()
+        .map(|p| format!("({},{})", p.row, p.col))
+        .collect();
+    cells.join(" -> ")
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    let (part1, part2) = solve(&input);
-    println!("part1: {}", part1);
-    println!("part2: {}", part2);
+    let rule = StepRule {
+        max_step: args.max_step,
+        descend: args.descend,
+        diagonal: args.diagonal,
+    };
+    let map = TopoMap::new(&input, rule);
+    for head in map.trailheads() {
+        if args.peaks {
+            let peaks: Vec<String> = map
+                .peaks_from(head)
+                .iter()
+                .map(|p| format!("({},{})", p.row, p.col))
+                .collect();
+            println!("({},{}): {}", head.row, head.col, peaks.join(" "));
+        }
+        if let Some(limit) = args.trails {
+            for trail in map.trails_from(head, limit) {
+                println!("{}", format_trail(&trail));
+            }
+        }
+    }
+    if args.heat {
+        let heat = map.heat_map();
+        for row in heat.chunks(map.grid.width as usize) {
+            let row: Vec<String> = row.iter().map(|n| format!("{:>4}", n)).collect();
+            println!("{}", row.join(""));
+        }
+    }
+    println!("part1: {}", map.score());
+    println!("part2: {}", map.rating());
 }
 
 #[cfg(test)]
@@ -75,6 +294,55 @@ mod tests {
 
     #[test]
     fn test_day9() {
-        assert_eq!(solve(TEST_GRID), (36, 81));
+        let map = TopoMap::new(TEST_GRID, StepRule::default());
+        assert_eq!((map.score(), map.rating()), (36, 81));
+    }
+
+    #[test]
+    fn test_day10_rules() {
+        let map = TopoMap::new(TEST_GRID, StepRule::default());
+        let heads = map.trailheads();
+        assert_eq!(heads.len(), 9);
+        assert_eq!(map.peaks_from(heads[0]).len(), 5);
+        let trails = map.trails_from(heads[0], 100);
+        assert_eq!(trails.len(), 20);
+        assert!(trails.iter().all(|t| t.len() == 10));
+        assert_eq!(map.trails_from(heads[0], 3).len(), 3);
+
+        let heat = map.heat_map();
+        let total: u64 = heads.iter().map(|h| heat[map.grid.point_to_idx(*h)]).sum();
+        assert_eq!(total, 81);
+
+        // walking down from the peaks finds the same trails backwards
+        let down = TopoMap::new(
+            TEST_GRID,
+            StepRule {
+                descend: true,
+                ..StepRule::default()
+            },
+        );
+        assert_eq!(down.rating(), 81);
+
+        let steep = TopoMap::new("0369\n....", StepRule::default());
+        assert_eq!(steep.rating(), 0);
+        let steep = TopoMap::new(
+            "0369\n....",
+            StepRule {
+                max_step: 3,
+                ..StepRule::default()
+            },
+        );
+        assert_eq!(steep.rating(), 1);
+        let diagonal = TopoMap::new(
+            "0.\n.1",
+            StepRule {
+                diagonal: true,
+                ..StepRule::default()
+            },
+        );
+        assert_eq!(
+            diagonal.successors(Point::new(0, 0)),
+            vec![Point::new(1, 1)]
+        );
     }
 }
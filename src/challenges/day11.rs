@@ -1,6 +1,9 @@
-use advent24::parse_number_list;
+use advent24::{
+    evolution::{Evolution, Overflow},
+    parse_number_list,
+};
 use clap;
-use std::{collections::HashMap, fs, str};
+use std::{fs, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -9,64 +12,67 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Blink this many times instead of 25 (or 75 with --part2)
+    #[arg(long)]
+    blinks: Option<usize>,
+
+    /// Memoize stone counts per (value, blinks left) instead of evolving the
+    /// whole multiset
+    #[clap(long, action)]
+    cached: bool,
 }
 
-pub fn blink(stones: HashMap<u64, u64>) -> HashMap<u64, u64> {
-    let mut res: HashMap<u64, u64> = HashMap::new();
-    for stone in stones.keys() {
-        let count = *stones.get(stone).unwrap();
-        let nstr = format!("{}", stone);
-        match stone {
-            0 => {
-                res.entry(1).and_modify(|v| *v += count).or_insert(count);
-            }
-            _ if nstr.len() % 2 == 0 && nstr.len() >= 2 => {
-                let first_half: u64 = (&nstr)[0..nstr.len() / 2].parse().unwrap();
-                res.entry(first_half)
-                    .and_modify(|v| *v += count)
-                    .or_insert(count);
-                let second_half: u64 = (&nstr)[nstr.len() / 2..nstr.len()].parse().unwrap();
-                res.entry(second_half)
-                    .and_modify(|v| *v += count)
-                    .or_insert(count);
-            }
-            n => {
-                res.entry(n * 2024)
-                    .and_modify(|v| *v += count)
-                    .or_insert(count);
-            }
-        }
+/// Split `n` into its left and right halves if it has an even number of digits
+pub fn split_digits(n: u64) -> Option<(u64, u64)> {
+    let digits = n.checked_ilog10()? + 1;
+    if digits % 2 != 0 {
+        return None;
     }
-    res
+    let half = u64::pow(10, digits / 2);
+    Some((n / half, n % half))
 }
 
-pub fn part1(input: &str) -> u64 {
-    let stones: Vec<u64> = parse_number_list(input);
-    let mut stone_map: HashMap<u64, u64> = HashMap::new();
-    for s in stones.iter() {
-        stone_map.entry(*s).and_modify(|v| *v += 1).or_insert(1);
-    }
-    for _ in 0..25 {
-        stone_map = blink(stone_map);
-    }
-    stone_map.values().sum::<u64>()
+/// The stones one stone turns into, or `Overflow` if multiplying it by 2024
+/// doesn't fit in a `u64`
+pub fn blink(stone: &u64) -> Result<Vec<u64>, Overflow> {
+    Ok(match *stone {
+        0 => vec![1],
+        n => match split_digits(n) {
+            Some((left, right)) => vec![left, right],
+            None => vec![n.checked_mul(2024).ok_or(Overflow)?],
+        },
+    })
 }
 
-pub fn part2(input: &str) -> u64 {
+pub fn solve(input: &str, blinks: usize, cached: bool) -> Result<u128, Overflow> {
     let stones: Vec<u64> = parse_number_list(input);
-    let mut stone_map: HashMap<u64, u64> = HashMap::new();
-    for s in stones.iter() {
-        stone_map.entry(*s).and_modify(|v| *v += 1).or_insert(1);
-    }
-    for _ in 0..75 {
-        stone_map = blink(stone_map);
+    let mut evolution = Evolution::new(blink);
+    if cached {
+        evolution.count_total(&stones, blinks)
+    } else {
+        evolution.run_total(&stones, blinks)
     }
-    stone_map.values().sum::<u64>()
+}
+
+pub fn part1(input: &str) -> u128 {
+    solve(input, 25, false).unwrap()
+}
+
+pub fn part2(input: &str) -> u128 {
+    solve(input, 75, false).unwrap()
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    if args.part2 {
+    if args.blinks.is_some() || args.cached {
+        let default_blinks = if args.part2 { 75 } else { 25 };
+        let blinks = args.blinks.unwrap_or(default_blinks);
+        match solve(&input, blinks, args.cached) {
+            Ok(res) => println!("{}", res),
+            Err(Overflow) => println!("stone value or count overflowed within {} blinks", blinks),
+        }
+    } else if args.part2 {
         let res = part2(&input);
         println!("{}", res);
     } else {
@@ -82,5 +88,16 @@ mod tests {
     #[test]
     fn test_day11() {
         assert_eq!(part1("125 17"), 55312);
+        assert_eq!(solve("125 17", 25, true), Ok(55312));
+        assert_eq!(solve("125 17", 6, false), Ok(22));
+        assert_eq!(split_digits(253000), Some((253, 0)));
+        assert_eq!(split_digits(1000), Some((10, 0)));
+        assert_eq!(split_digits(999), None);
+        assert_eq!(blink(&0), Ok(vec![1]));
+        assert_eq!(blink(&1), Ok(vec![2024]));
+        // 19 digits, so it gets multiplied and no longer fits in a u64
+        assert_eq!(blink(&1_000_000_000_000_000_000), Err(Overflow));
+        assert_eq!(solve("1000000000000000000", 1, false), Err(Overflow));
+        assert_eq!(solve("1000000000000000000", 1, true), Err(Overflow));
     }
 }
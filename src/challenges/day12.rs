@@ -1,6 +1,6 @@
-use advent24::bytegrid::ByteGrid;
+use advent24::bytegrid::{signed_area, ByteGrid, Point, Region};
 use clap;
-use std::{fs, str};
+use std::{fmt, fs, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -9,6 +9,108 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// List every region with its area, perimeter, sides and prices
+    #[clap(long, action)]
+    report: bool,
+
+    /// Write the region outlines to an SVG file
+    #[arg(long)]
+    svg: Option<String>,
+
+    /// Size of one garden plot in the SVG, in pixels
+    #[arg(long, default_value_t = 10)]
+    cell_size: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionReport {
+    pub plant: u8,
+    pub area: i32,
+    pub perimeter: i32,
+    pub sides: i32,
+    /// Outer outlines first, then holes, as corner polygons
+    pub boundaries: Vec<Vec<Point>>,
+}
+
+impl RegionReport {
+    pub fn new(region: &Region) -> Self {
+        RegionReport {
+            plant: region.byte(),
+            area: region.area(),
+            perimeter: region.perimeter(),
+            sides: region.sides(),
+            boundaries: region.boundaries(),
+        }
+    }
+
+    pub fn price(&self) -> i32 {
+        self.area * self.perimeter
+    }
+
+    pub fn bulk_price(&self) -> i32 {
+        self.area * self.sides
+    }
+
+    pub fn holes(&self) -> usize {
+        self.boundaries
+            .iter()
+            .filter(|b| signed_area(b) < 0)
+            .count()
+    }
+}
+
+impl fmt::Display for RegionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: area {}, perimeter {}, sides {}, holes {}, price {}, bulk price {}",
+            self.plant as char,
+            self.area,
+            self.perimeter,
+            self.sides,
+            self.holes(),
+            self.price(),
+            self.bulk_price()
+        )
+    }
+}
+
+pub fn report(grid: &ByteGrid) -> Vec<RegionReport> {
+    grid.to_regions().iter().map(RegionReport::new).collect()
+}
+
+/// Every region as one filled path, coloured by plant, with holes cut out
+pub fn to_svg(grid: &ByteGrid, regions: &[RegionReport], cell_size: i32) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        grid.width * cell_size,
+        grid.height * cell_size
+    );
+    for region in regions {
+        let hue = (region.plant as u32 * 47) % 360;
+        let mut path = String::new();
+        for outline in region.boundaries.iter() {
+            for (i, p) in outline.iter().enumerate() {
+                let cmd = if i == 0 { 'M' } else { 'L' };
+                path.push_str(&format!(
+                    "{}{} {} ",
+                    cmd,
+                    p.col * cell_size,
+                    p.row * cell_size
+                ));
+            }
+            path.push_str("Z ");
+        }
+        svg.push_str(&format!(
+            "  <path d=\"{}\" fill=\"hsl({}, 60%, 60%)\" fill-rule=\"evenodd\" stroke=\"black\"><title>{}</title></path>\n",
+            path.trim_end(),
+            hue,
+            region
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
 }
 
 pub fn solve(input: &str) -> (i32, i32) {
@@ -21,6 +123,18 @@ pub fn solve(input: &str) -> (i32, i32) {
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
+    if args.report || args.svg.is_some() {
+        let grid = ByteGrid::new(&input);
+        let regions = report(&grid);
+        if args.report {
+            for region in regions.iter() {
+                println!("{}", region);
+            }
+        }
+        if let Some(path) = &args.svg {
+            fs::write(path, to_svg(&grid, &regions, args.cell_size)).expect("I/O error");
+        }
+    }
     let (p1, p2) = solve(&input);
     println!("part1: {}", p1);
     println!("part2: {}", p2);
@@ -83,4 +197,47 @@ AA.";
         assert_eq!(solve(CROSS).1, 76);
         assert_eq!(solve(DIAG).1, 48);
     }
+
+    #[test]
+    fn test_day12_boundaries() {
+        for garden in [
+            SMOL_EXAMPLE,
+            LARGER_EXAMPLE,
+            ESHAPED_EXAMPLE,
+            THIRD_EXAMPLE,
+            CROSS,
+            DIAG,
+        ] {
+            for region in report(&ByteGrid::new(garden)) {
+                // every corner of an outline starts a new side
+                let corners: usize = region.boundaries.iter().map(|b| b.len()).sum();
+                assert_eq!(corners as i32, region.sides);
+            }
+        }
+
+        let regions = report(&ByteGrid::new(THIRD_EXAMPLE));
+        let a = regions.iter().find(|r| r.plant == b'A').unwrap();
+        // the two B blocks touch at a corner, so they share one outline
+        assert_eq!(a.holes(), 1);
+        assert_eq!(
+            a.boundaries[0],
+            vec![
+                Point::new(0, 0),
+                Point::new(0, 6),
+                Point::new(6, 6),
+                Point::new(6, 0)
+            ]
+        );
+        // twice the area: the outer square minus the two holes
+        let area: i64 = a.boundaries.iter().map(|b| signed_area(b)).sum();
+        assert_eq!(area, 2 * a.area as i64);
+
+        let svg = to_svg(
+            &ByteGrid::new(ESHAPED_EXAMPLE),
+            &report(&ByteGrid::new(ESHAPED_EXAMPLE)),
+            10,
+        );
+        assert_eq!(svg.matches("<path").count(), 3);
+        assert!(svg.contains("M0 0 L50 0 "));
+    }
 }
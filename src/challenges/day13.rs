@@ -1,7 +1,10 @@
-use advent24::parse_number_list;
+use advent24::{
+    linalg::{min_cost_nonneg, solve, Rational, Solution},
+    parse_number_list,
+};
 use clap;
 use itertools::Itertools;
-use std::{fs, str};
+use std::{fmt, fs, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -10,6 +13,80 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Tokens per press of button A
+    #[arg(long, default_value_t = Rules::default().cost_a)]
+    cost_a: i128,
+
+    /// Tokens per press of button B
+    #[arg(long, default_value_t = Rules::default().cost_b)]
+    cost_b: i128,
+
+    /// Most presses allowed per button (defaults to 100 for part 1, no limit
+    /// for part 2)
+    #[arg(long)]
+    limit: Option<i128>,
+
+    /// Print the outcome for every machine
+    #[clap(long, action)]
+    explain: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    pub cost_a: i128,
+    pub cost_b: i128,
+    /// Most presses allowed per button
+    pub limit: Option<i128>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            cost_a: 3,
+            cost_b: 1,
+            limit: Some(100),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Presses {
+    pub a: i128,
+    pub b: i128,
+    pub cost: i128,
+}
+
+impl fmt::Display for Presses {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "A x{} + B x{} = {} tokens", self.a, self.b, self.cost)
+    }
+}
+
+/// Why a machine's prize can't be won
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unsolvable {
+    /// The only way to reach the prize takes a fractional number of presses
+    NonInteger,
+    /// The only way to reach the prize takes a negative number of presses
+    Negative,
+    /// The only way to reach the prize takes more presses than allowed
+    OverLimit,
+    /// The buttons move in the same direction and no allowed combination of
+    /// them lands on the prize
+    Parallel,
+}
+
+impl fmt::Display for Unsolvable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            Unsolvable::NonInteger => "needs a fractional number of presses",
+            Unsolvable::Negative => "needs a negative number of presses",
+            Unsolvable::OverLimit => "needs more presses than allowed",
+            Unsolvable::Parallel => "buttons are parallel and can't reach the prize",
+        };
+        write!(f, "{}", reason)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -26,61 +103,39 @@ pub struct ClawMachine {
 // }
 
 impl ClawMachine {
-    pub fn token_cost(&self, max: i128) -> i128 {
-        let a1 = self.button_a.0;
-        let b1 = self.button_b.0;
-        let c1 = self.prize.0;
-        let a2 = self.button_a.1;
-        let b2 = self.button_b.1;
-        let c2 = self.prize.1;
-
-        let a_buttons_numer = (c1 * b2) - (b1 * c2);
-        let a_buttons_denom = (a1 * b2) - (b1 * a2);
-        let mut a_buttons = 0;
-
-        if a_buttons_denom != 0 && a_buttons_numer % a_buttons_denom == 0 {
-            a_buttons = a_buttons_numer / a_buttons_denom;
-            //     println!(
-            //         "
-            // ({:<6} * {:<6}) - ({:<6} * {:<6})        {:<6}
-            // --------------------------------------   =   --------  = {}
-            // ({:<6} * {:<6}) - ({:<6} * {:<6})        {:<6}
-            // ",
-            //         c1, b2, b1, c2, a_buttons_numer, a_buttons, a1, b2, b1, a2, a_buttons_denom
-            //     );
-            if a_buttons > max || a_buttons < 0 {
-                return 0;
+    /// The cheapest way to win the prize, or why there isn't one
+    pub fn play(&self, rules: &Rules) -> Result<Presses, Unsolvable> {
+        let a = vec![
+            vec![self.button_a.0, self.button_b.0],
+            vec![self.button_a.1, self.button_b.1],
+        ];
+        let b = [self.prize.0, self.prize.1];
+        let costs = [rules.cost_a, rules.cost_b];
+        let presses = match solve(&a, &b) {
+            Solution::Unique(x) => {
+                let x: Vec<i128> = x
+                    .iter()
+                    .map(Rational::to_integer)
+                    .collect::<Option<_>>()
+                    .ok_or(Unsolvable::NonInteger)?;
+                if x.iter().any(|v| *v < 0) {
+                    return Err(Unsolvable::Negative);
+                }
+                if x.iter().any(|v| rules.limit.is_some_and(|max| *v > max)) {
+                    return Err(Unsolvable::OverLimit);
+                }
+                x
             }
-        }
-
-        let b_buttons_numer = (a1 * c2) - (c1 * a2);
-        let b_buttons_denom = (a1 * b2) - (b1 * a2);
-        let mut b_buttons = 0;
-
-        if b_buttons_denom != 0 && b_buttons_numer % b_buttons_denom == 0 {
-            b_buttons = b_buttons_numer / b_buttons_denom;
-            //     println!(
-            //         "
-            // ({:<6} * {:<6}) - ({:<6} * {:<6})        {:<6}
-            // --------------------------------------   =   --------  = {}
-            // ({:<6} * {:<6}) - ({:<6} * {:<6})        {:<6}
-            //     ",
-            //         a1, c2, c1, a2, b_buttons_numer, b_buttons, a1, b2, b1, a2, b_buttons_denom
-            //     );
-            if b_buttons > max || b_buttons < 0 {
-                return 0;
+            Solution::Infinite => {
+                min_cost_nonneg(&a, &b, &costs, rules.limit).ok_or(Unsolvable::Parallel)?
             }
-        }
-
-        if (
-            self.button_a.0 * a_buttons + self.button_b.0 * b_buttons,
-            self.button_a.1 * a_buttons + self.button_b.1 * b_buttons,
-        ) == (self.prize.0, self.prize.1)
-        {
-            a_buttons * 3 + b_buttons
-        } else {
-            0
-        }
+            Solution::Inconsistent => return Err(Unsolvable::Parallel),
+        };
+        Ok(Presses {
+            a: presses[0],
+            b: presses[1],
+            cost: presses[0] * rules.cost_a + presses[1] * rules.cost_b,
+        })
     }
 }
 
@@ -117,35 +172,44 @@ pub fn parse(input: &str) -> Vec<ClawMachine> {
     res
 }
 
-pub fn part1(input: &str) -> i128 {
-    let claw_machines = parse(input);
-    let mut total_tokens = 0;
-    for claw_machine in claw_machines {
-        total_tokens += claw_machine.token_cost(100);
-    }
-    total_tokens
-}
+const PRIZE_OFFSET: i128 = 10000000000000;
 
-pub fn part2(input: &str) -> i128 {
-    let claw_machines = parse(input);
+/// Tokens needed to win every prize that can be won, optionally printing
+/// each machine's outcome
+pub fn total_cost(machines: &[ClawMachine], rules: &Rules, explain: bool) -> i128 {
     let mut total_tokens = 0;
-    for mut claw_machine in claw_machines {
-        claw_machine.prize.0 += 10000000000000;
-        claw_machine.prize.1 += 10000000000000;
-        total_tokens += claw_machine.token_cost(i128::MAX);
+    for (i, machine) in machines.iter().enumerate() {
+        let outcome = machine.play(rules);
+        if explain {
+            match outcome {
+                Ok(presses) => println!("machine {}: {}", i + 1, presses),
+                Err(reason) => println!("machine {}: {}", i + 1, reason),
+            }
+        }
+        total_tokens += outcome.map_or(0, |p| p.cost);
     }
     total_tokens
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
+    let mut machines = parse(&input);
     if args.part2 {
-        let res = part2(&input);
-        println!("{}", res);
-    } else {
-        let res = part1(&input);
-        println!("{}", res);
+        for machine in machines.iter_mut() {
+            machine.prize.0 += PRIZE_OFFSET;
+            machine.prize.1 += PRIZE_OFFSET;
+        }
     }
+    let rules = Rules {
+        cost_a: args.cost_a,
+        cost_b: args.cost_b,
+        limit: args.limit.or(if args.part2 {
+            None
+        } else {
+            Rules::default().limit
+        }),
+    };
+    println!("{}", total_cost(&machines, &rules, args.explain));
 }
 
 #[cfg(test)]
@@ -172,6 +236,73 @@ Prize: X=18641, Y=10279
 
     #[test]
     fn test_day9() {
-        assert_eq!(part1(TEST_GRID), 480);
+        assert_eq!(total_cost(&parse(TEST_GRID), &Rules::default(), false), 480);
+
+        // parallel buttons used to be reported as impossible
+        let machine = ClawMachine {
+            button_a: (3, 3),
+            button_b: (1, 1),
+            prize: (10, 10),
+        };
+        assert_eq!(machine.play(&Rules::default()).map(|p| p.cost), Ok(10));
+    }
+
+    #[test]
+    fn test_day13_outcomes() {
+        let machines = parse(TEST_GRID);
+        let rules = Rules::default();
+        assert_eq!(
+            machines[0].play(&rules),
+            Ok(Presses {
+                a: 80,
+                b: 40,
+                cost: 280
+            })
+        );
+        assert_eq!(machines[1].play(&rules), Err(Unsolvable::NonInteger));
+        // 38 A and 86 B presses would do, if B could be pressed 86 times
+        let machine = ClawMachine {
+            button_a: (1, 0),
+            button_b: (0, 1),
+            prize: (38, 86),
+        };
+        assert_eq!(machine.play(&rules).map(|p| p.cost), Ok(200));
+        let capped = Rules {
+            limit: Some(50),
+            ..rules
+        };
+        assert_eq!(machine.play(&capped), Err(Unsolvable::OverLimit));
+        let machine = ClawMachine {
+            button_a: (1, 0),
+            button_b: (2, 1),
+            prize: (1, 2),
+        };
+        assert_eq!(machine.play(&rules), Err(Unsolvable::Negative));
+
+        let parallel = ClawMachine {
+            button_a: (2, 2),
+            button_b: (1, 1),
+            prize: (10, 10),
+        };
+        assert_eq!(
+            parallel.play(&rules),
+            Ok(Presses {
+                a: 0,
+                b: 10,
+                cost: 10
+            })
+        );
+        // with B pricier, two units of distance per token from A wins
+        let pricey_b = Rules {
+            cost_a: 1,
+            cost_b: 3,
+            limit: None,
+        };
+        assert_eq!(parallel.play(&pricey_b).map(|p| (p.a, p.b)), Ok((5, 0)));
+        let off_line = ClawMachine {
+            prize: (10, 11),
+            ..parallel
+        };
+        assert_eq!(off_line.play(&rules), Err(Unsolvable::Parallel));
     }
 }
@@ -1,7 +1,7 @@
-use advent24::{bytegrid::ByteGrid, extract_numbers};
+use advent24::{bytegrid::ByteGrid, extract_numbers, linalg::crt, nth_state};
 use clap;
 use std::{
-    fs,
+    fmt, fs,
     io::Write,
     str::{self, FromStr},
 };
@@ -13,6 +13,18 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Print the safety factor at this time instead of after 100 seconds
+    #[arg(long)]
+    time: Option<usize>,
+
+    /// Write the first 10000 frames and their stats to this file
+    #[arg(long)]
+    dump: Option<String>,
+
+    /// Print the picture found for part 2 along with its stats
+    #[clap(long, short, action)]
+    verbose: bool,
 }
 
 #[derive(Debug, Default)]
@@ -57,14 +69,11 @@ impl FromStr for Robot {
     }
 }
 
-pub fn part1(input: &str, w: i32, h: i32) -> i32 {
-    let mut robots: Vec<Robot> = vec![];
-    for line in input.lines() {
-        robots.push(line.parse().unwrap());
-    }
-    let final_positions: Vec<(i32, i32)> = robots.iter().map(|r| r.pos_after(100, w, h)).collect();
+/// Product of the number of robots in each quadrant, ignoring the middle row
+/// and column
+pub fn safety_factor(positions: &[(i32, i32)], w: i32, h: i32) -> i32 {
     let mut q = [0; 4];
-    for pos in final_positions.iter() {
+    for pos in positions.iter() {
         let mut quad_idx = 0;
         if pos.0 == w / 2 || pos.1 == h / 2 {
             continue;
@@ -77,34 +86,221 @@ pub fn part1(input: &str, w: i32, h: i32) -> i32 {
         }
         q[quad_idx] += 1;
     }
-    dbg!(&robots);
-    dbg!(&final_positions);
-    dbg!(&q);
     q[0] * q[1] * q[2] * q[3]
 }
 
-pub fn part2(input: &str, w: i32, h: i32) -> i32 {
-    let mut file = fs::File::create("output.txt").unwrap();
+pub fn part1(input: &str, w: i32, h: i32) -> i32 {
     let mut robots: Vec<Robot> = vec![];
     for line in input.lines() {
         robots.push(line.parse().unwrap());
     }
+    let final_positions: Vec<(i32, i32)> = robots.iter().map(|r| r.pos_after(100, w, h)).collect();
+    dbg!(&robots);
+    dbg!(&final_positions);
+    safety_factor(&final_positions, w, h)
+}
+
+/// Safety factor at an arbitrarily large time. The robots all wrap around the
+/// same grid, so the whole swarm repeats and only one period is simulated.
+pub fn safety_factor_at(input: &str, w: i32, h: i32, t: usize) -> i32 {
+    let robots: Vec<Robot> = input.lines().map(|l| l.parse().unwrap()).collect();
+    let initial: Vec<(i32, i32)> = robots.iter().map(|r| (r.x, r.y)).collect();
+    let step = |positions: &Vec<(i32, i32)>| -> Vec<(i32, i32)> {
+        positions
+            .iter()
+            .zip(robots.iter())
+            .map(|(pos, r)| ((pos.0 + r.dx).rem_euclid(w), (pos.1 + r.dy).rem_euclid(h)))
+            .collect()
+    };
+    safety_factor(&nth_state(&initial, step, t), w, h)
+}
+
+pub fn render(positions: &[(i32, i32)], w: i32, h: i32) -> ByteGrid {
     let mut board = ByteGrid::new_empty(b' ', w, h);
-    for t in 0..10000 {
-        let positions: Vec<(i32, i32)> = robots.iter().map(|r| r.pos_after(t, w, h)).collect();
+    for pos in positions {
+        board[(pos.1, pos.0)] = b'#';
+    }
+    board
+}
+
+pub fn variance(values: impl Iterator<Item = i32> + Clone) -> f64 {
+    let n = values.clone().count() as f64;
+    let mean = values.clone().map(|v| v as f64).sum::<f64>() / n;
+    values.map(|v| (v as f64 - mean).powi(2)).sum::<f64>() / n
+}
+
+/// Measures of how "picture-like" a frame is. Random noise has high variance,
+/// a middling safety factor, tiny connected regions and no crowded rows or
+/// columns; a drawing has the opposite.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    pub t: usize,
+    pub x_variance: f64,
+    pub y_variance: f64,
+    pub safety_factor: i32,
+    pub largest_region: i32,
+    pub busiest_row: usize,
+    pub busiest_col: usize,
+}
+
+impl FrameStats {
+    pub fn new(positions: &[(i32, i32)], t: usize, w: i32, h: i32) -> Self {
+        let board = render(positions, w, h);
+        let largest_region = board
+            .to_regions()
+            .iter()
+            .filter(|r| r.byte() == b'#')
+            .map(|r| r.area())
+            .max()
+            .unwrap_or(0);
+        let mut row_counts = vec![0; h as usize];
+        let mut col_counts = vec![0; w as usize];
         for pos in positions {
-            board[(pos.1, pos.0)] = b'#';
+            col_counts[pos.0 as usize] += 1;
+            row_counts[pos.1 as usize] += 1;
+        }
+        FrameStats {
+            t,
+            x_variance: variance(positions.iter().map(|p| p.0)),
+            y_variance: variance(positions.iter().map(|p| p.1)),
+            safety_factor: safety_factor(positions, w, h),
+            largest_region,
+            busiest_row: row_counts.into_iter().max().unwrap_or(0),
+            busiest_col: col_counts.into_iter().max().unwrap_or(0),
         }
-        writeln!(file, "{}:\n{}\n\n", t, &board).unwrap();
-        board.data.fill(b' ');
     }
-    0
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "t={} variance=({:.1}, {:.1}) safety_factor={} largest_region={} busiest_row={} busiest_col={}",
+            self.t,
+            self.x_variance,
+            self.y_variance,
+            self.safety_factor,
+            self.largest_region,
+            self.busiest_row,
+            self.busiest_col
+        )
+    }
+}
+
+/// Score candidate frames against each other. Every statistic is scaled by
+/// its largest value among `frames` (flipped where smaller is better) and the
+/// results are summed, so a frame has to look structured on most measures to
+/// come out on top.
+pub fn scores(frames: &[FrameStats]) -> Vec<f64> {
+    let max = |f: fn(&FrameStats) -> f64| frames.iter().map(f).fold(0.0, f64::max);
+    let ratio = |v: f64, max: f64| if max > 0.0 { v / max } else { 0.0 };
+    let max_variance = max(|s| s.x_variance + s.y_variance);
+    let max_safety = max(|s| s.safety_factor as f64);
+    let max_region = max(|s| s.largest_region as f64);
+    let max_row = max(|s| s.busiest_row as f64);
+    let max_col = max(|s| s.busiest_col as f64);
+    frames
+        .iter()
+        .map(|s| {
+            (1.0 - ratio(s.x_variance + s.y_variance, max_variance))
+                + (1.0 - ratio(s.safety_factor as f64, max_safety))
+                + ratio(s.largest_region as f64, max_region)
+                + ratio(s.busiest_row as f64, max_row)
+                + ratio(s.busiest_col as f64, max_col)
+        })
+        .collect()
+}
+
+/// How many of the tightest x (and y) offsets to combine into candidates
+const CANDIDATES_PER_AXIS: usize = 3;
+
+/// The first time the robots are most likely to be drawing something.
+///
+/// x positions repeat every `w` seconds and y positions every `h` seconds, so
+/// the tightest x clusterings only need to be searched for in the first `w`
+/// frames, and likewise for y. Every pairing of the few tightest x and y
+/// offsets gives a candidate frame through the chinese remainder theorem, and
+/// the candidate that `scores` best (earliest on ties) wins. If `w` and `h`
+/// share a factor some pairs never line up, and those are skipped.
+pub fn find_picture(robots: &[Robot], w: i32, h: i32) -> Option<usize> {
+    let tightest = |period: i32, coord: fn(&(i32, i32)) -> i32| -> Vec<i128> {
+        let mut by_variance: Vec<(i32, f64)> = (0..period)
+            .map(|t| {
+                let positions: Vec<(i32, i32)> =
+                    robots.iter().map(|r| r.pos_after(t, w, h)).collect();
+                (t, variance(positions.iter().map(coord)))
+            })
+            .collect();
+        by_variance.sort_by(|a, b| a.1.total_cmp(&b.1));
+        by_variance
+            .into_iter()
+            .take(CANDIDATES_PER_AXIS)
+            .map(|(t, _)| t as i128)
+            .collect()
+    };
+    let mut candidates: Vec<usize> = vec![];
+    for tx in tightest(w, |p| p.0) {
+        for ty in tightest(h, |p| p.1) {
+            if let Some((t, _)) = crt(&[(tx, w as i128), (ty, h as i128)]) {
+                candidates.push(t as usize);
+            }
+        }
+    }
+    candidates.sort();
+    candidates.dedup();
+    let frames: Vec<FrameStats> = candidates
+        .iter()
+        .map(|t| FrameStats::new(&positions_at(robots, *t, w, h), *t, w, h))
+        .collect();
+    let scores = scores(&frames);
+    let mut best = 0;
+    for (i, score) in scores.iter().enumerate() {
+        if *score > scores[best] {
+            best = i;
+        }
+    }
+    candidates.get(best).copied()
+}
+
+pub fn positions_at(robots: &[Robot], t: usize, w: i32, h: i32) -> Vec<(i32, i32)> {
+    robots.iter().map(|r| r.pos_after(t as i32, w, h)).collect()
+}
+
+pub fn part2(input: &str, w: i32, h: i32) -> Option<usize> {
+    let robots: Vec<Robot> = input.lines().map(|l| l.parse().unwrap()).collect();
+    find_picture(&robots, w, h)
+}
+
+/// Write every frame up to `frames` to `path` along with its stats, for
+/// checking by eye
+pub fn dump_frames(input: &str, w: i32, h: i32, frames: usize, path: &str) {
+    let mut file = fs::File::create(path).unwrap();
+    let robots: Vec<Robot> = input.lines().map(|l| l.parse().unwrap()).collect();
+    for t in 0..frames {
+        let positions = positions_at(&robots, t, w, h);
+        let stats = FrameStats::new(&positions, t, w, h);
+        writeln!(file, "{}:\n{}\n\n", stats, render(&positions, w, h)).unwrap();
+    }
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    if args.part2 {
-        let res = part2(&input, 101, 103);
+    if let Some(path) = &args.dump {
+        dump_frames(&input, 101, 103, 10000, path);
+    } else if let Some(t) = args.time {
+        let res = safety_factor_at(&input, 101, 103, t);
+        println!("{}", res);
+    } else if args.part2 {
+        let Some(res) = part2(&input, 101, 103) else {
+            println!("no picture found");
+            return;
+        };
+        if args.verbose {
+            let robots: Vec<Robot> = input.lines().map(|l| l.parse().unwrap()).collect();
+            let positions = positions_at(&robots, res, 101, 103);
+            println!("{}", render(&positions, 101, 103));
+            println!("{}", FrameStats::new(&positions, res, 101, 103));
+        }
         println!("{}", res);
     } else {
         let res = part1(&input, 101, 103);
@@ -134,5 +330,65 @@ p=9,5 v=-3,-3
     #[test]
     fn test_day9() {
         assert_eq!(part1(TEST_GRID, 11, 7), 12);
+        assert_eq!(safety_factor_at(TEST_GRID, 11, 7, 100), 12);
+        // 11 * 7 = 77 steps per period
+        assert_eq!(
+            safety_factor_at(TEST_GRID, 11, 7, 77 * 1_000_000_000 + 100),
+            12
+        );
+    }
+
+    #[test]
+    fn test_day14_stats() {
+        let robots: Vec<Robot> = TEST_GRID.lines().map(|l| l.parse().unwrap()).collect();
+        let positions: Vec<(i32, i32)> = robots.iter().map(|r| r.pos_after(100, 11, 7)).collect();
+        let stats = FrameStats::new(&positions, 100, 11, 7);
+        assert_eq!(stats.safety_factor, 12);
+        assert_eq!(stats.busiest_row, 3);
+        assert_eq!(stats.largest_region, 2);
+        assert!(variance([1, 1, 1].into_iter()) == 0.0);
+        assert!(variance([0, 2].into_iter()) == 1.0);
+
+        // a frame that looks tidier on every measure gets every point
+        let tidy = FrameStats {
+            t: 1,
+            x_variance: 0.0,
+            y_variance: 0.0,
+            safety_factor: 0,
+            largest_region: 12,
+            busiest_row: 12,
+            busiest_col: 12,
+        };
+        let scored = scores(&[stats, tidy]);
+        assert_eq!(scored[1], 5.0);
+        assert!(scored[0] < scored[1]);
+
+        // a vertical line at x = 3 forms at t = 2 and a horizontal one at y = 1
+        // at t = 3, so both coincide for the first time at t = 24
+        let robots: Vec<Robot> = (0..5)
+            .map(|i| Robot {
+                x: 3 - 2 * (i - 2) + 11 * 5,
+                y: 1 - 3 * (i - 2) + 7 * 5,
+                dx: i - 2,
+                dy: i - 2,
+            })
+            .map(|r| Robot {
+                x: r.x.rem_euclid(11),
+                y: r.y.rem_euclid(7),
+                ..r
+            })
+            .collect();
+        assert_eq!(find_picture(&robots, 11, 7), Some(24));
+
+        // 4 and 6 share a factor, so pairs of offsets with different parity
+        // have no common time and are skipped. A lone robot ties everywhere,
+        // leaving the earliest frame.
+        let robot = Robot {
+            x: 0,
+            y: 1,
+            dx: 1,
+            dy: 1,
+        };
+        assert_eq!(find_picture(&[robot], 4, 6), Some(0));
     }
 }
@@ -1,6 +1,6 @@
-use advent24::bytegrid::{ByteGrid, Point, EAST, NORTH, SOUTH, WEST};
+use advent24::bytegrid::{ByteGrid, Direction, Point};
 use clap;
-use std::{fs, str};
+use std::{collections::HashSet, fmt, fs, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -9,236 +9,502 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Widen the map by this factor (defaults to 2 with --part2)
+    #[arg(long)]
+    scale: Option<usize>,
+
+    /// Extra box shapes, with `/` between rows and spaces for holes (e.g. `AB/ C`)
+    #[arg(long)]
+    shape: Vec<String>,
+
+    /// Glyph to add up GPS coordinates for (defaults to the left edge of a box)
+    #[arg(long)]
+    glyph: Option<char>,
+
+    /// Print the warehouse after every move
+    #[clap(long, action)]
+    show: bool,
+
+    /// Stop after this many moves and print the warehouse
+    #[arg(long)]
+    stop_after: Option<usize>,
+
+    /// Write the move log as CSV to this file
+    #[arg(long)]
+    log: Option<String>,
+
+    /// Find the first move where the warehouse differs from this file of
+    /// grids, one per move (starting with the initial state) separated by
+    /// blank lines
+    #[arg(long)]
+    reference: Option<String>,
 }
 
-fn take_step(world: &mut ByteGrid, from: Point, towards: u8) -> Option<Point> {
-    let my_char = world[from];
-    if let Some(heading) = match towards {
-        b'^' => Some(NORTH),
-        b'>' => Some(EAST),
-        b'<' => Some(WEST),
-        b'v' => Some(SOUTH),
-        _ => None,
-    } {
-        let next_pos = from + heading;
-        let behind_next_pos = from + heading + heading;
-        if !world.is_valid_point(behind_next_pos) {
-            return Some(from);
-        }
-        match (world[next_pos], world[behind_next_pos]) {
-            (b'O', b'.') => {
-                world[from] = b'.';
-                world[next_pos] = my_char;
-                world[behind_next_pos] = b'O';
-                Some(next_pos)
-            }
-            (b'O', b'O') => {
-                if let Some(point) = take_step(world, next_pos, towards) {
-                    if point != next_pos {
-                        world[from] = b'.';
-                        world[next_pos] = my_char;
-                        world[behind_next_pos] = b'O';
-                        Some(next_pos)
-                    } else {
-                        Some(from)
-                    }
-                } else {
-                    Some(from)
+/// A kind of box, as glyphs at offsets from its first cell in reading order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shape {
+    cells: Vec<(Point, u8)>,
+}
+
+impl Shape {
+    pub fn parse(s: &str) -> Self {
+        let mut cells: Vec<(Point, u8)> = vec![];
+        for (row, line) in s.split('/').enumerate() {
+            for (col, b) in line.bytes().enumerate() {
+                if b != b' ' {
+                    cells.push((Point::new(row as i32, col as i32), b));
                 }
             }
-            (b'.', b'.') | (b'.', b'#') | (b'.', b'O') => {
-                world[from] = b'.';
-                world[next_pos] = my_char;
-                Some(next_pos)
-            }
-            _ => Some(from),
         }
-    } else {
-        None
+        let anchor = cells.first().expect("empty shape").0;
+        Shape {
+            cells: cells.into_iter().map(|(p, b)| (p - anchor, b)).collect(),
+        }
     }
 }
 
-pub fn part1(input: &str) -> i32 {
-    let parts: Vec<&str> = input.split("\n\n").collect();
-    let mut world = ByteGrid::new(parts[0]);
-    let bytes_to_points = world.bytes_to_points();
-    let mut character = bytes_to_points[&b'@'][0];
-    println!("{}\n", &world);
-    for c in parts[1].chars() {
-        if let Some(p) = take_step(&mut world, character, c as u8) {
-            println!("{}\n", &world);
-            character = p;
-        }
+/// `O` boxes plus their widened forms
+pub fn default_shapes() -> Vec<Shape> {
+    ["O", "[]", "[-]"].map(Shape::parse).to_vec()
+}
+
+/// How an `O` box looks once widened by `factor`, e.g. `[--]` at 4x
+fn box_glyphs(factor: usize) -> String {
+    if factor <= 1 {
+        return "O".to_owned();
+    }
+    format!("[{}]", "-".repeat(factor - 2))
+}
+
+pub fn box_shape(factor: usize) -> Shape {
+    Shape::parse(&box_glyphs(factor))
+}
+
+/// Widen every cell of the map, e.g. `O` becomes `[]` at 2x and `[-]` at 3x
+pub fn scale(map: &str, factor: usize) -> String {
+    if factor <= 1 {
+        return map.to_owned();
     }
-    let updated_bytes_to_points = world.bytes_to_points();
-    let mut score = 0;
-    println!("{}\n", &world);
-    for p in updated_bytes_to_points[&b'O'].iter() {
-        score += 100 * p.row + p.col;
+    let mut scaled = String::with_capacity(map.len() * factor);
+    for c in map.chars() {
+        match c {
+            '\n' => scaled.push(c),
+            '@' => {
+                scaled.push('@');
+                scaled.extend(std::iter::repeat_n('.', factor - 1));
+            }
+            'O' => scaled.push_str(&box_glyphs(factor)),
+            _ => scaled.extend(std::iter::repeat_n(c, factor)),
+        }
     }
-    score
+    scaled
 }
 
-pub fn push_vert(world: &mut ByteGrid, from: Point, direction: Point) -> bool {
-    let backup = world.data.clone();
-    let from_byte = world[from];
-    let points_to_check = match from_byte {
-        b'[' => [from + direction, from + direction + EAST],
-        b']' => [from + direction + WEST, from + direction],
-        _ => unreachable!(),
-    };
-    for p in points_to_check {
-        if world.is_valid_point(p) {
-            let next_byte = world[p];
-            match next_byte {
-                b'[' | b']' => {
-                    if !push_vert(world, p, direction) {
-                        world.data = backup;
-                        return false;
-                    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    pub cells: Vec<Point>,
+    pub glyphs: Vec<u8>,
+}
+
+/// Walls and floor in a grid, with the robot and boxes tracked on top
+pub struct Warehouse {
+    floor: ByteGrid,
+    pub robot: Point,
+    pub pieces: Vec<Piece>,
+    /// Which piece covers each cell
+    owner: Vec<Option<usize>>,
+}
+
+impl Warehouse {
+    pub fn new(map: &str, shapes: &[Shape]) -> Self {
+        let grid = ByteGrid::new(map);
+        let mut floor = grid.clone();
+        let mut owner: Vec<Option<usize>> = vec![None; grid.data.len()];
+        let mut pieces: Vec<Piece> = vec![];
+        let mut robot = Point::default();
+        for idx in 0..grid.data.len() {
+            let pos = grid.idx_to_point(idx);
+            if grid[idx] == b'@' {
+                robot = pos;
+                floor[idx] = b'.';
+                continue;
+            }
+            if owner[idx].is_some() {
+                continue;
+            }
+            let fits = |shape: &&Shape| {
+                shape.cells.iter().all(|(offset, b)| {
+                    let p = pos + *offset;
+                    grid.get_point(p) == Some(b) && owner[grid.point_to_idx(p)].is_none()
+                })
+            };
+            if let Some(shape) = shapes.iter().find(fits) {
+                let cells: Vec<Point> = shape.cells.iter().map(|(o, _)| pos + *o).collect();
+                for p in cells.iter() {
+                    owner[grid.point_to_idx(*p)] = Some(pieces.len());
+                    floor[*p] = b'.';
                 }
-                b'.' => continue,
-                _ => {
-                    world.data = backup;
-                    return false;
+                pieces.push(Piece {
+                    cells,
+                    glyphs: shape.cells.iter().map(|(_, b)| *b).collect(),
+                });
+            }
+        }
+        Warehouse {
+            floor,
+            robot,
+            pieces,
+            owner,
+        }
+    }
+
+    /// Every piece that would have to move, or `None` if something hits a wall
+    fn pushed_by(&self, direction: Direction) -> Option<Vec<usize>> {
+        let step = direction.to_point();
+        let mut moved: Vec<usize> = vec![];
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut frontier: Vec<Point> = vec![self.robot + step];
+        while let Some(p) = frontier.pop() {
+            if self.floor.get_point(p) != Some(&b'.') {
+                return None;
+            }
+            if let Some(piece) = self.owner[self.floor.point_to_idx(p)] {
+                if seen.insert(piece) {
+                    moved.push(piece);
+                    frontier.extend(self.pieces[piece].cells.iter().map(|c| *c + step));
                 }
             }
         }
+        Some(moved)
     }
-    world[points_to_check[0]] = b'[';
-    world[points_to_check[1]] = b']';
-    world[from] = b'.';
-    match from_byte {
-        b'[' => world[from + EAST] = b'.',
-        b']' => world[from + WEST] = b'.',
-        _ => unreachable!(),
+
+    fn glyph_at(&self, p: Point) -> u8 {
+        if p == self.robot {
+            return b'@';
+        }
+        match self.owner[self.floor.point_to_idx(p)] {
+            Some(piece) => {
+                let piece = &self.pieces[piece];
+                let i = piece.cells.iter().position(|c| *c == p).unwrap();
+                piece.glyphs[i]
+            }
+            None => self.floor[p],
+        }
     }
-    true
-}
 
-pub fn push_east(world: &mut ByteGrid, from: Point) -> bool {
-    let p = from + EAST * 2;
-    if world.is_valid_point(p) {
-        let next_byte = world[p];
-        match next_byte {
-            b'[' => {
-                if !push_east(world, p) {
-                    return false;
-                }
+    fn shift(&mut self, pieces: &[usize], step: Point) {
+        for piece in pieces {
+            for p in self.pieces[*piece].cells.iter() {
+                self.owner[self.floor.point_to_idx(*p)] = None;
             }
-            b'.' => (),
-            _ => return false,
         }
+        for piece in pieces {
+            for p in self.pieces[*piece].cells.iter_mut() {
+                *p = *p + step;
+                self.owner[self.floor.point_to_idx(*p)] = Some(*piece);
+            }
+        }
+        self.robot = self.robot + step;
     }
-    world[p] = b']';
-    world[p - EAST] = b'[';
-    world[from] = b'.';
-    true
-}
 
-pub fn push_west(world: &mut ByteGrid, from: Point) -> bool {
-    let p = from + WEST * 2;
-    if world.is_valid_point(p) {
-        let next_byte = world[p];
-        match next_byte {
-            b']' => {
-                if !push_west(world, p) {
-                    return false;
-                }
+    /// Move the robot, pushing whatever is in the way. Nothing moves unless
+    /// everything can.
+    pub fn apply(&mut self, direction: Direction) -> Move {
+        let Some(pushed) = self.pushed_by(direction) else {
+            return Move {
+                direction,
+                succeeded: false,
+                pushed: vec![],
+                changes: vec![],
+            };
+        };
+        let step = direction.to_point();
+        let mut touched: Vec<Point> = vec![self.robot, self.robot + step];
+        for piece in pushed.iter() {
+            let cells = &self.pieces[*piece].cells;
+            touched.extend(cells.iter().flat_map(|p| [*p, *p + step]));
+        }
+        touched.sort();
+        touched.dedup();
+        let before: Vec<u8> = touched.iter().map(|p| self.glyph_at(*p)).collect();
+        self.shift(&pushed, step);
+        let changes = touched
+            .into_iter()
+            .zip(before)
+            .map(|(p, was)| (p, was, self.glyph_at(p)))
+            .filter(|(_, was, now)| was != now)
+            .collect();
+        Move {
+            direction,
+            succeeded: true,
+            pushed,
+            changes,
+        }
+    }
+
+    pub fn undo(&mut self, last: &Move) {
+        if last.succeeded {
+            self.shift(&last.pushed, last.direction.turn_around().to_point());
+        }
+    }
+
+    pub fn render(&self) -> ByteGrid {
+        let mut world = self.floor.clone();
+        for piece in self.pieces.iter() {
+            for (p, b) in piece.cells.iter().zip(piece.glyphs.iter()) {
+                world[*p] = *b;
             }
-            b'.' => (),
-            _ => return false,
         }
+        world[self.robot] = b'@';
+        world
+    }
+
+    /// Sum of `100 * row + col` over every box cell drawn with `glyph`
+    pub fn gps(&self, glyph: u8) -> i32 {
+        self.pieces
+            .iter()
+            .flat_map(|piece| piece.cells.iter().zip(piece.glyphs.iter()))
+            .filter(|(_, b)| **b == glyph)
+            .map(|(p, _)| 100 * p.row + p.col)
+            .sum()
     }
-    world[p] = b'[';
-    world[p - WEST] = b']';
-    world[from] = b'.';
-    true
 }
 
-fn take_step_part2(world: &mut ByteGrid, from: Point, towards: u8) -> Option<Point> {
-    let my_char = world[from];
-    if let Some(heading) = match towards {
-        b'^' => Some(NORTH),
-        b'>' => Some(EAST),
-        b'<' => Some(WEST),
-        b'v' => Some(SOUTH),
-        _ => None,
-    } {
-        let next_pos = from + heading;
-        match world[next_pos] {
-            b'[' | b']' => match heading {
-                NORTH | SOUTH => {
-                    if push_vert(world, next_pos, heading) {
-                        world[from] = b'.';
-                        world[next_pos] = my_char;
-                        Some(next_pos)
-                    } else {
-                        Some(from)
-                    }
-                }
-                EAST => {
-                    if push_east(world, next_pos) {
-                        world[from] = b'.';
-                        world[next_pos] = my_char;
-                        Some(next_pos)
-                    } else {
-                        Some(from)
-                    }
-                }
-                WEST => {
-                    if push_west(world, next_pos) {
-                        world[from] = b'.';
-                        world[next_pos] = my_char;
-                        Some(next_pos)
-                    } else {
-                        Some(from)
-                    }
-                }
-                _ => unreachable!(),
-            },
-            b'.' => {
-                world[from] = b'.';
-                world[next_pos] = my_char;
-                Some(next_pos)
-            }
-            _ => Some(from),
+/// One robot move and what it changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub direction: Direction,
+    /// Whether the robot moved at all
+    pub succeeded: bool,
+    /// Indices of the pieces that were pushed
+    pub pushed: Vec<usize>,
+    /// Every cell that changed, with its glyph before and after
+    pub changes: Vec<(Point, u8, u8)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MoveStats {
+    pub moves: usize,
+    pub blocked: usize,
+    /// Blocked moves per direction, in `Direction::CARDINALS` order
+    pub blocked_by_direction: [usize; 4],
+}
+
+impl fmt::Display for MoveStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} moves, {} blocked", self.moves, self.blocked)?;
+        for (d, n) in Direction::CARDINALS.iter().zip(self.blocked_by_direction) {
+            write!(f, ", {} {}", d.to_arrow() as char, n)?;
         }
-    } else {
-        None
+        Ok(())
     }
 }
 
-pub fn part2(input: &str) -> i32 {
-    let parts: Vec<&str> = input.split("\n\n").collect();
-    let mut new_world = parts[0].to_owned();
-    new_world = new_world.replace("#", "##");
-    new_world = new_world.replace(".", "..");
-    new_world = new_world.replace("O", "[]");
-    new_world = new_world.replace("@", "@.");
-    let mut world = ByteGrid::new(&new_world);
-
-    let bytes_to_points = world.bytes_to_points();
-    let mut character = bytes_to_points[&b'@'][0];
-    println!("{}\n", &world);
-    for c in parts[1].chars() {
-        if let Some(p) = take_step_part2(&mut world, character, c as u8) {
-            character = p;
-            println!("{}\n", &world);
+/// A warehouse and its list of moves, with a log of everything applied so
+/// far that can be undone or replayed
+pub struct Simulation {
+    pub warehouse: Warehouse,
+    moves: Vec<Direction>,
+    log: Vec<Move>,
+}
+
+impl Simulation {
+    pub fn new(warehouse: Warehouse, moves: Vec<Direction>) -> Self {
+        Simulation {
+            warehouse,
+            moves,
+            log: vec![],
+        }
+    }
+
+    /// How many moves have been applied
+    pub fn position(&self) -> usize {
+        self.log.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn log(&self) -> &[Move] {
+        &self.log
+    }
+
+    pub fn forward(&mut self) -> Option<&Move> {
+        let direction = *self.moves.get(self.log.len())?;
+        let applied = self.warehouse.apply(direction);
+        self.log.push(applied);
+        self.log.last()
+    }
+
+    pub fn undo(&mut self) -> Option<Move> {
+        let last = self.log.pop()?;
+        self.warehouse.undo(&last);
+        Some(last)
+    }
+
+    /// Go to the state after `n` moves, forwards or backwards
+    pub fn seek(&mut self, n: usize) {
+        let n = n.min(self.moves.len());
+        while self.log.len() > n {
+            self.undo();
+        }
+        while self.log.len() < n {
+            self.forward();
+        }
+    }
+
+    pub fn run(&mut self) {
+        self.seek(self.moves.len());
+    }
+
+    pub fn stats(&self) -> MoveStats {
+        let mut stats = MoveStats {
+            moves: self.log.len(),
+            ..MoveStats::default()
+        };
+        for m in self.log.iter().filter(|m| !m.succeeded) {
+            stats.blocked += 1;
+            stats.blocked_by_direction[m.direction as usize / 2] += 1;
         }
+        stats
     }
-    let updated_bytes_to_points = world.bytes_to_points();
-    let mut score = 0;
-    println!("{}\n", &world);
-    for p in updated_bytes_to_points[&b'['].iter() {
-        score += 100 * p.row + p.col;
+
+    /// The log as CSV, one row per applied move
+    pub fn export_log(&self) -> String {
+        let mut csv = String::from("move,direction,succeeded,pushed,changes\n");
+        for (i, m) in self.log.iter().enumerate() {
+            let changes: Vec<String> = m
+                .changes
+                .iter()
+                .map(|(p, was, now)| {
+                    format!("{}:{}:{}{}", p.row, p.col, *was as char, *now as char)
+                })
+                .collect();
+            csv += &format!(
+                "{},{},{},{},{}\n",
+                i + 1,
+                m.direction.to_arrow() as char,
+                m.succeeded,
+                m.pushed.len(),
+                changes.join(" ")
+            );
+        }
+        csv
     }
-    score
+
+    /// The first move after which the warehouse no longer matches
+    /// `reference`, which holds the expected state after 0, 1, 2... moves.
+    /// Assumes that once the states diverge they stay different.
+    pub fn bisect(&mut self, reference: &[ByteGrid]) -> Option<usize> {
+        let matches = |sim: &mut Simulation, n: usize| {
+            sim.seek(n);
+            sim.warehouse.render().data == reference[n].data
+        };
+        let last = reference.len().checked_sub(1)?.min(self.moves.len());
+        if !matches(self, 0) {
+            return Some(0);
+        }
+        if matches(self, last) {
+            return None;
+        }
+        let (mut lo, mut hi) = (0, last);
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if matches(self, mid) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Some(hi)
+    }
+}
+
+pub fn parse_moves(s: &str) -> Vec<Direction> {
+    s.bytes().filter_map(Direction::from_byte).collect()
+}
+
+/// Parse the map widened by `factor` and its moves. The box shape for
+/// `factor` is always recognised, even if `shapes` doesn't include it.
+pub fn load(input: &str, factor: usize, shapes: &[Shape]) -> Simulation {
+    let (map, moves) = input.split_once("\n\n").expect("no moves");
+    let mut shapes = shapes.to_vec();
+    let widened = box_shape(factor);
+    if !shapes.contains(&widened) {
+        shapes.push(widened);
+    }
+    Simulation::new(
+        Warehouse::new(&scale(map, factor), &shapes),
+        parse_moves(moves),
+    )
+}
+
+pub fn simulate(input: &str, factor: usize, shapes: &[Shape], glyph: u8, show: bool) -> i32 {
+    let mut sim = load(input, factor, shapes);
+    while sim.forward().is_some() {
+        if show {
+            println!("{}\n", sim.warehouse.render());
+        }
+    }
+    sim.warehouse.gps(glyph)
+}
+
+pub fn part1(input: &str) -> i32 {
+    simulate(input, 1, &default_shapes(), b'O', false)
+}
+
+pub fn part2(input: &str) -> i32 {
+    simulate(input, 2, &default_shapes(), b'[', false)
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    if args.part2 {
+    let factor = args.scale.unwrap_or(if args.part2 { 2 } else { 1 });
+    let mut shapes = default_shapes();
+    shapes.extend(args.shape.iter().map(|s| Shape::parse(s)));
+    let glyph = match args.glyph {
+        Some(c) => c as u8,
+        None if factor > 1 => b'[',
+        None => b'O',
+    };
+    if let Some(reference) = &args.reference {
+        let reference = fs::read_to_string(reference).expect("I/O error");
+        let grids: Vec<ByteGrid> = reference
+            .split("\n\n")
+            .filter(|g| !g.trim().is_empty())
+            .map(|g| ByteGrid::new(g.trim_start_matches('\n')))
+            .collect();
+        let mut sim = load(&input, factor, &shapes);
+        match sim.bisect(&grids) {
+            Some(n) => {
+                println!("diverged after move {}", n);
+                println!("expected:\n{}", grids[n]);
+                println!("actual:\n{}", sim.warehouse.render());
+            }
+            None => println!("matches the reference"),
+        }
+    } else if args.stop_after.is_some() || args.log.is_some() {
+        let mut sim = load(&input, factor, &shapes);
+        match args.stop_after {
+            Some(n) => sim.seek(n),
+            None => sim.run(),
+        }
+        if let Some(log) = &args.log {
+            fs::write(log, sim.export_log()).expect("I/O error");
+        }
+        println!("after {} of {} moves:", sim.position(), sim.len());
+        if let Some(last) = sim.log().last() {
+            println!("last move {:?} changed {:?}", last.direction, last.changes);
+        }
+        println!("{}", sim.warehouse.render());
+        println!("{}", sim.stats());
+        println!("{}", sim.warehouse.gps(glyph));
+    } else if args.scale.is_some() || !args.shape.is_empty() || args.glyph.is_some() || args.show {
+        let res = simulate(&input, factor, &shapes, glyph, args.show);
+        println!("{}", res);
+    } else if args.part2 {
         let res = part2(&input);
         println!("{}", res);
     } else {
@@ -319,5 +585,81 @@ v^>>v>^<^>>>v>^^<<<<<<<v<^^v<<<^^";
         assert_eq!(part2(TEST_GRID_2), 9021);
         assert_eq!(part2(TEST_GRID_3), 1732);
         assert_eq!(part2(TEST_GRID_4), 1216);
+
+        assert_eq!(scale("#.O@\n", 3), "###...[-]@..\n");
+        let (map, _) = TEST_GRID_2.split_once("\n\n").unwrap();
+        assert_eq!(
+            Warehouse::new(&scale(map, 3), &default_shapes())
+                .pieces
+                .len(),
+            Warehouse::new(map, &default_shapes()).pieces.len()
+        );
+        // boxes wider than the default shapes still move
+        assert_eq!(scale("#.O@\n", 4), "####....[--]@...\n");
+        let mut wide = load(TEST_GRID_2, 4, &default_shapes());
+        assert_eq!(
+            wide.warehouse.pieces.len(),
+            load(TEST_GRID_2, 1, &default_shapes())
+                .warehouse
+                .pieces
+                .len()
+        );
+        wide.run();
+        assert!(wide.warehouse.gps(b'[') > 0);
+
+        // an L-shaped piece drags its foot along when pushed up
+        let mut warehouse = Warehouse::new(
+            "#####\n#...#\n#L..#\n#LL.#\n#.@.#\n#####",
+            &[Shape::parse("L /LL")],
+        );
+        assert_eq!(warehouse.pieces.len(), 1);
+        let pushed = warehouse.apply(Direction::North);
+        assert!(pushed.succeeded);
+        assert_eq!(pushed.changes.len(), 5);
+        assert_eq!(
+            format!("{}", warehouse.render()),
+            "#####\n#L..#\n#LL.#\n#.@.#\n#...#\n#####\n"
+        );
+        // blocked by the wall, so nothing moves
+        assert!(!warehouse.apply(Direction::North).succeeded);
+        assert_eq!(warehouse.robot, Point::new(3, 2));
+        assert_eq!(warehouse.gps(b'L'), 101 + 201 + 202);
+        warehouse.undo(&pushed);
+        assert_eq!(warehouse.robot, Point::new(4, 2));
+        assert_eq!(warehouse.gps(b'L'), 201 + 301 + 302);
+    }
+
+    #[test]
+    fn test_day15_log() {
+        let mut sim = load(TEST_GRID, 1, &default_shapes());
+        sim.seek(5);
+        let after_five = sim.warehouse.render();
+        sim.run();
+        assert_eq!(sim.warehouse.gps(b'O'), 2028);
+        assert_eq!(sim.position(), 15);
+        let stats = sim.stats();
+        assert_eq!(stats.blocked, 5);
+        assert_eq!(stats.blocked_by_direction, [1, 1, 1, 2]);
+        let log = sim.export_log();
+        assert_eq!(log.lines().count(), 16);
+        assert!(log.lines().nth(1).unwrap().starts_with("1,<,false,0,"));
+
+        sim.seek(5);
+        assert_eq!(sim.warehouse.render().data, after_five.data);
+        sim.undo();
+        assert_eq!(sim.position(), 4);
+
+        // a reference that goes wrong at move 9
+        let mut reference: Vec<ByteGrid> = (0..=15)
+            .map(|n| {
+                sim.seek(n);
+                sim.warehouse.render()
+            })
+            .collect();
+        assert_eq!(sim.bisect(&reference), None);
+        for grid in reference[9..].iter_mut() {
+            grid[0] = b'?';
+        }
+        assert_eq!(sim.bisect(&reference), Some(9));
     }
 }
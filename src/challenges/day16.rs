@@ -1,7 +1,13 @@
-use advent24::bytegrid::{ByteGrid, Point, EAST, NORTH, SOUTH, WEST};
+use advent24::{
+    bytegrid::{ByteGrid, Direction, Point},
+    graph::Graph,
+};
 use clap;
-use pathfinding::prelude::*;
-use std::{collections::HashSet, fs, str};
+use pathfinding::prelude::yen;
+use std::{
+    collections::{HashMap, HashSet},
+    fs, str,
+};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -10,191 +16,245 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Cost of moving one tile forward
+    #[arg(long, default_value_t = 1)]
+    step_cost: u32,
+
+    /// Cost of turning 90 degrees
+    #[arg(long, default_value_t = 1000)]
+    turn_cost: u32,
+
+    /// Print the K cheapest distinct paths
+    #[arg(long)]
+    paths: Option<usize>,
+
+    /// Print the cost of the best path through the tile at ROW,COL
+    #[arg(long, value_delimiter = ',')]
+    through: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Costs {
+    pub step: u32,
+    pub turn: u32,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+impl Default for Costs {
+    fn default() -> Self {
+        Costs {
+            step: 1,
+            turn: 1000,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 pub struct Reindeer {
     pub pos: Point,
-    pub heading: Point,
+    pub heading: Direction,
 }
 
 impl Reindeer {
     pub fn new(pos: Point) -> Self {
-        Reindeer { pos, heading: EAST }
+        Reindeer {
+            pos,
+            heading: Direction::East,
+        }
+    }
+
+    pub fn successors(&self, costs: Costs) -> Vec<(Reindeer, u32)> {
+        let turn = |heading: Direction| Reindeer {
+            pos: self.pos,
+            heading,
+        };
+        vec![
+            (
+                Reindeer {
+                    pos: self.pos + self.heading.to_point(),
+                    heading: self.heading,
+                },
+                costs.step,
+            ),
+            (turn(self.heading.turn_left()), costs.turn),
+            (turn(self.heading.turn_right()), costs.turn),
+        ]
     }
+}
 
-    pub fn successors(&self) -> Vec<(Reindeer, u32)> {
-        match self.heading {
-            NORTH => vec![
-                (
-                    Reindeer {
-                        pos: self.pos + NORTH,
-                        heading: NORTH,
-                    },
-                    1,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: WEST,
-                    },
-                    1000,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: EAST,
-                    },
-                    1000,
-                ),
-            ],
-            SOUTH => vec![
-                (
-                    Reindeer {
-                        pos: self.pos + SOUTH,
-                        heading: SOUTH,
-                    },
-                    1,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: WEST,
-                    },
-                    1000,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: EAST,
-                    },
-                    1000,
-                ),
-            ],
-            EAST => vec![
-                (
-                    Reindeer {
-                        pos: self.pos + EAST,
-                        heading: EAST,
-                    },
-                    1,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: NORTH,
-                    },
-                    1000,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: SOUTH,
-                    },
-                    1000,
-                ),
-            ],
-            WEST => vec![
-                (
-                    Reindeer {
-                        pos: self.pos + WEST,
-                        heading: WEST,
-                    },
-                    1,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: NORTH,
-                    },
-                    1000,
-                ),
-                (
-                    Reindeer {
-                        pos: self.pos,
-                        heading: SOUTH,
-                    },
-                    1000,
-                ),
-            ],
-            _ => unreachable!(),
+pub struct Maze {
+    pub grid: ByteGrid,
+    pub start: Reindeer,
+    pub goal: Point,
+    graph: Graph<Reindeer, u32>,
+}
+
+impl Maze {
+    pub fn new(input: &str, costs: Costs) -> Self {
+        let grid = ByteGrid::new(input);
+        let bytes_to_points = grid.bytes_to_points();
+        let start = Reindeer::new(bytes_to_points[&b'S'][0]);
+        let goal = bytes_to_points[&b'E'][0];
+        let mut graph = Graph::new();
+        for (idx, b) in grid.data.iter().enumerate() {
+            if *b == b'#' {
+                continue;
+            }
+            for heading in Direction::CARDINALS {
+                let reindeer = Reindeer {
+                    pos: grid.idx_to_point(idx),
+                    heading,
+                };
+                graph.add_node(reindeer);
+                for (next, cost) in reindeer.successors(costs) {
+                    if grid.get_point(next.pos).is_some_and(|b| *b != b'#') {
+                        graph.add_edge(reindeer, next, cost);
+                    }
+                }
+            }
+        }
+        Maze {
+            grid,
+            start,
+            goal,
+            graph,
         }
     }
 
-    pub fn dist(&self, other: &Reindeer) -> u32 {
-        self.pos.row.abs_diff(other.pos.row) + self.pos.col.abs_diff(other.pos.col)
+    fn goal_states(&self) -> impl Iterator<Item = Reindeer> + '_ {
+        Direction::CARDINALS.into_iter().map(|heading| Reindeer {
+            pos: self.goal,
+            heading,
+        })
     }
-}
 
-pub fn part1(input: &str) -> u32 {
-    let mut world = ByteGrid::new(input);
-    let bytes_to_points = world.bytes_to_points();
-
-    let start = Reindeer::new(bytes_to_points[&b'S'][0]);
-    let goal = Reindeer::new(bytes_to_points[&b'E'][0]);
-    let successors = |p: &Reindeer| -> Vec<(Reindeer, u32)> {
-        p.successors()
-            .into_iter()
-            .filter(|r| world.is_valid_point(r.0.pos) && world[r.0.pos] != b'#')
-            .collect()
-    };
-    let heuristic = |p: &Reindeer| -> u32 { p.dist(&goal) };
-    let success = |p: &Reindeer| p.pos == goal.pos;
-
-    let (path, total_cost) = astar(&start, successors, heuristic, success).expect("no path found");
-    for p in path {
-        world[p.pos] = match p.heading {
-            NORTH => b'^',
-            EAST => b'>',
-            SOUTH => b'V',
-            WEST => b'<',
-            _ => b'?',
-        };
+    /// Cheapest cost from the start to every state
+    fn costs_from_start(&self) -> HashMap<Reindeer, u32> {
+        self.graph.dijkstra(self.start)
     }
-    world[start.pos] = b'S';
-    world[goal.pos] = b'E';
-    println!("{}", &world);
-    total_cost
-}
 
-pub fn part2(input: &str) -> u32 {
-    let mut world = ByteGrid::new(input);
-    let bytes_to_points = world.bytes_to_points();
-
-    let start = Reindeer::new(bytes_to_points[&b'S'][0]);
-    let goal = Reindeer::new(bytes_to_points[&b'E'][0]);
-    let successors = |p: &Reindeer| -> Vec<(Reindeer, u32)> {
-        p.successors()
-            .into_iter()
-            .filter(|r| world.is_valid_point(r.0.pos) && world[r.0.pos] != b'#')
-            .collect()
-    };
-    let heuristic = |p: &Reindeer| -> u32 { p.dist(&goal) };
-    let success = |p: &Reindeer| p.pos == goal.pos;
+    /// Cheapest cost from every state to the goal, facing any way
+    fn costs_to_goal(&self) -> HashMap<Reindeer, u32> {
+        let reversed = self.graph.transpose();
+        let mut best: HashMap<Reindeer, u32> = HashMap::new();
+        for goal in self.goal_states() {
+            for (state, cost) in reversed.dijkstra(goal) {
+                let entry = best.entry(state).or_insert(cost);
+                *entry = (*entry).min(cost);
+            }
+        }
+        best
+    }
+
+    pub fn cheapest(&self) -> Option<u32> {
+        let costs = self.costs_from_start();
+        self.goal_states()
+            .filter_map(|g| costs.get(&g))
+            .min()
+            .copied()
+    }
+
+    /// For every reachable tile, the cost of the cheapest start-to-goal path
+    /// that passes through it
+    pub fn best_through(&self) -> HashMap<Point, u32> {
+        let from_start = self.costs_from_start();
+        let to_goal = self.costs_to_goal();
+        let mut best: HashMap<Point, u32> = HashMap::new();
+        for (state, cost) in from_start {
+            let Some(rest) = to_goal.get(&state) else {
+                continue;
+            };
+            let entry = best.entry(state.pos).or_insert(cost + rest);
+            *entry = (*entry).min(cost + rest);
+        }
+        best
+    }
 
-    let (paths, _) =
-        astar_bag_collect(&start, successors, heuristic, success).expect("no path found");
+    /// Tiles on at least one of the cheapest paths
+    pub fn best_tiles(&self) -> usize {
+        let Some(cheapest) = self.cheapest() else {
+            return 0;
+        };
+        self.best_through()
+            .values()
+            .filter(|c| **c == cheapest)
+            .count()
+    }
 
-    let mut tiles: HashSet<Point> = HashSet::new();
-    for path in paths {
-        for reindeer in path {
-            tiles.insert(reindeer.pos);
+    /// Up to `k` of the cheapest paths that visit different sequences of
+    /// tiles (paths that only differ in how they turn on the spot count once)
+    pub fn k_cheapest(&self, k: usize) -> Vec<(Vec<Reindeer>, u32)> {
+        let successors = |r: &Reindeer| self.graph.successors(*r).to_vec();
+        let success = |r: &Reindeer| r.pos == self.goal;
+        let mut wanted = k;
+        loop {
+            let found = yen(&self.start, successors, success, wanted);
+            let exhausted = found.len() < wanted;
+            let mut seen: HashSet<Vec<Point>> = HashSet::new();
+            let distinct: Vec<(Vec<Reindeer>, u32)> = found
+                .into_iter()
+                .filter(|(path, _)| {
+                    let mut tiles: Vec<Point> = path.iter().map(|r| r.pos).collect();
+                    tiles.dedup();
+                    seen.insert(tiles)
+                })
+                .take(k)
+                .collect();
+            if distinct.len() == k || exhausted {
+                return distinct;
+            }
+            wanted *= 2;
         }
     }
 
-    for tile in tiles.iter() {
-        world[*tile] = b'*';
+    /// The maze with `path` drawn on it as arrows
+    pub fn draw(&self, path: &[Reindeer]) -> ByteGrid {
+        let mut world = self.grid.clone();
+        for p in path {
+            world[p.pos] = p.heading.to_arrow();
+        }
+        world[self.start.pos] = b'S';
+        world[self.goal] = b'E';
+        world
     }
+}
 
-    let b_to_p = world.bytes_to_points();
-    println!("{}", &world);
+pub fn part1(input: &str) -> u32 {
+    let maze = Maze::new(input, Costs::default());
+    maze.cheapest().expect("no path found")
+}
 
-    b_to_p[&b'*'].len() as u32
+pub fn part2(input: &str) -> u32 {
+    let maze = Maze::new(input, Costs::default());
+    maze.best_tiles() as u32
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    if args.part2 {
+    let costs = Costs {
+        step: args.step_cost,
+        turn: args.turn_cost,
+    };
+    if args.paths.is_some() || !args.through.is_empty() || costs != Costs::default() {
+        let maze = Maze::new(&input, costs);
+        if let Some(k) = args.paths {
+            for (path, cost) in maze.k_cheapest(k) {
+                println!("{}\ncost {}\n", maze.draw(&path), cost);
+            }
+        }
+        if let [row, col] = args.through[..] {
+            let through = maze.best_through();
+            match through.get(&Point::new(row, col)) {
+                Some(cost) => println!("best path through ({}, {}): {}", row, col, cost),
+                None => println!("no path through ({}, {})", row, col),
+            }
+        }
+        if args.part2 {
+            println!("{}", maze.best_tiles());
+        } else {
+            println!("{}", maze.cheapest().expect("no path found"));
+        }
+    } else if args.part2 {
         let res = part2(&input);
         println!("{}", res);
     } else {
@@ -250,4 +310,27 @@ mod tests {
         assert_eq!(part2(TEST_GRID), 45);
         assert_eq!(part2(TEST_GRID_2), 64);
     }
+
+    #[test]
+    fn test_day16_paths() {
+        let maze = Maze::new(TEST_GRID, Costs::default());
+        let through = maze.best_through();
+        assert_eq!(through[&maze.start.pos], 7036);
+        // the dead end at the top left costs extra turns and steps to visit
+        assert!(through[&Point::new(1, 1)] > 7036);
+
+        let best = maze.k_cheapest(3);
+        assert_eq!(best.len(), 3);
+        assert_eq!(best[0].1, 7036);
+        assert!(best.windows(2).all(|w| w[0].1 <= w[1].1));
+        let drawn = maze.draw(&best[0].0);
+        assert_eq!(drawn[maze.start.pos], b'S');
+        assert!(drawn.count(b'^') > 0);
+
+        // with free turns it's just the shortest walk
+        let maze = Maze::new(TEST_GRID, Costs { step: 1, turn: 0 });
+        let tiles = Graph::from_grid(&maze.grid, |b| b != b'#');
+        let walk = tiles.bfs(maze.start.pos)[&maze.goal] as u32;
+        assert_eq!(maze.cheapest(), Some(walk));
+    }
 }
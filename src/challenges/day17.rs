@@ -1,7 +1,7 @@
 use advent24::extract_numbers;
 use clap;
 use itertools::Itertools;
-use std::{fs, iter::zip, str};
+use std::{collections::HashMap, fmt::Write, fs, iter::zip, str};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -10,6 +10,14 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
+
+    /// Stop part 1 after this many instructions
+    #[arg(long)]
+    max_steps: Option<usize>,
+
+    /// Write an execution trace of part 1 to this file (.csv or .json)
+    #[arg(long)]
+    trace: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -37,6 +45,161 @@ pub struct Computer {
     pub output: Vec<u64>,
     pub i: usize,
     pub registers: [u64; 3],
+    pub trace: Option<Trace>,
+}
+
+pub fn mnemonic(opcode: u64) -> &'static str {
+    match opcode {
+        0 => "adv",
+        1 => "bxl",
+        2 => "bst",
+        3 => "jnz",
+        4 => "bxc",
+        5 => "out",
+        6 => "bdv",
+        7 => "cdv",
+        _ => "???",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub ip: usize,
+    pub opcode: u64,
+    /// The operand as the instruction sees it: combo operands are resolved
+    /// to the register value they refer to
+    pub operand: u64,
+    pub before: [u64; 3],
+    pub after: [u64; 3],
+    pub output: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub records: Vec<TraceRecord>,
+}
+
+impl Trace {
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("ip,opcode,mnemonic,operand,a_before,b_before,c_before,a_after,b_after,c_after,output\n");
+        for r in self.records.iter() {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                r.ip,
+                r.opcode,
+                mnemonic(r.opcode),
+                r.operand,
+                r.before[REG_A],
+                r.before[REG_B],
+                r.before[REG_C],
+                r.after[REG_A],
+                r.after[REG_B],
+                r.after[REG_C],
+                r.output.map(|o| o.to_string()).unwrap_or_default()
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let records = self
+            .records
+            .iter()
+            .map(|r| {
+                format!(
+                    "{{\"ip\":{},\"opcode\":{},\"mnemonic\":\"{}\",\"operand\":{},\"before\":[{}],\"after\":[{}],\"output\":{}}}",
+                    r.ip,
+                    r.opcode,
+                    mnemonic(r.opcode),
+                    r.operand,
+                    r.before.iter().join(","),
+                    r.after.iter().join(","),
+                    r.output.map(|o| o.to_string()).unwrap_or("null".to_string())
+                )
+            })
+            .join(",\n  ");
+        format!("[\n  {records}\n]\n")
+    }
+
+    /// How many times the instruction at each program index was executed
+    pub fn hit_counts(&self, program_len: usize) -> Vec<usize> {
+        let mut counts = vec![0; program_len];
+        for r in self.records.iter() {
+            counts[r.ip] += 1;
+        }
+        counts
+    }
+
+    /// Indices of program bytes that were never read as an opcode or operand
+    pub fn never_executed(&self, program_len: usize) -> Vec<usize> {
+        let mut touched = vec![false; program_len];
+        for r in self.records.iter() {
+            touched[r.ip] = true;
+            if r.ip + 1 < program_len {
+                touched[r.ip + 1] = true;
+            }
+        }
+        (0..program_len).filter(|i| !touched[*i]).collect()
+    }
+}
+
+/// Snapshot of everything that determines what the computer does next. The
+/// output is deliberately excluded: if the same `State` is seen twice the
+/// program will repeat forever no matter what it has printed so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct State {
+    pub i: usize,
+    pub registers: [u64; 3],
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub max_steps: Option<usize>,
+    pub max_output: Option<usize>,
+    pub detect_loops: bool,
+}
+
+impl Limits {
+    pub fn max_steps(mut self, steps: usize) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    pub fn max_output(mut self, len: usize) -> Self {
+        self.max_output = Some(len);
+        self
+    }
+
+    pub fn detect_loops(mut self) -> Self {
+        self.detect_loops = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunResult {
+    /// The instruction pointer ran off the end of the program
+    Halted {
+        steps: usize,
+        state: State,
+    },
+    StepLimit {
+        steps: usize,
+        state: State,
+    },
+    OutputLimit {
+        steps: usize,
+        state: State,
+    },
+    /// `state` was already seen after `first_seen` steps, so the program
+    /// will cycle with a period of `steps - first_seen` forever
+    Looped {
+        steps: usize,
+        first_seen: usize,
+        state: State,
+    },
 }
 
 static REG_A: usize = 0;
@@ -50,6 +213,43 @@ impl Computer {
             output: vec![],
             i: 0,
             registers,
+            trace: None,
+        }
+    }
+
+    pub fn enable_trace(&mut self) {
+        self.trace = Some(Trace::default());
+    }
+
+    /// The value of the current instruction's operand, without the side effects
+    /// of actually executing it
+    fn current_operand(&self) -> u64 {
+        let raw = self.program[self.i + 1];
+        match (self.program[self.i], Operand::combo(raw)) {
+            (0 | 2 | 5 | 6 | 7, Operand::Register(r)) => self.registers[r as usize],
+            _ => raw,
+        }
+    }
+
+    fn record(&mut self, before: State, operand: Option<u64>) {
+        let Some(operand) = operand else {
+            return;
+        };
+        let opcode = self.program[before.i];
+        let record = TraceRecord {
+            ip: before.i,
+            opcode,
+            operand,
+            before: before.registers,
+            after: self.registers,
+            output: if opcode == 5 {
+                self.output.last().copied()
+            } else {
+                None
+            },
+        };
+        if let Some(trace) = &mut self.trace {
+            trace.records.push(record);
         }
     }
 
@@ -86,6 +286,8 @@ impl Computer {
         if self.i >= self.program.len() {
             return false;
         }
+        let before = self.state();
+        let operand = self.trace.as_ref().map(|_| self.current_operand());
         match self.program[self.i] {
             0 => {
                 // The adv instruction (opcode 0) performs division. The numerator is the value in the A register.
@@ -150,6 +352,7 @@ impl Computer {
             }
             _ => unreachable!(),
         }
+        self.record(before, operand);
         true
     }
 
@@ -157,6 +360,8 @@ impl Computer {
         if self.i >= self.program.len() {
             return false;
         }
+        let before = self.state();
+        let operand = self.trace.as_ref().map(|_| self.current_operand());
         print!("program [ ");
         for (i, p) in self.program.iter().enumerate() {
             if i == self.i {
@@ -292,18 +497,56 @@ impl Computer {
             }
             _ => unreachable!(),
         }
+        self.record(before, operand);
         println!();
         true
     }
 
-    pub fn execute_to_end(&mut self) {
-        while self.execute() {}
+    pub fn state(&self) -> State {
+        State {
+            i: self.i,
+            registers: self.registers,
+        }
+    }
+
+    /// Run until the program halts or any of `limits` is reached
+    pub fn execute_to_end(&mut self, limits: &Limits) -> RunResult {
+        let mut seen: HashMap<State, usize> = HashMap::new();
+        let mut steps = 0;
+        loop {
+            let state = self.state();
+            if limits
+                .max_output
+                .is_some_and(|max| self.output.len() >= max)
+            {
+                return RunResult::OutputLimit { steps, state };
+            }
+            if limits.max_steps.is_some_and(|max| steps >= max) {
+                return RunResult::StepLimit { steps, state };
+            }
+            if limits.detect_loops {
+                if let Some(first_seen) = seen.insert(state, steps) {
+                    return RunResult::Looped {
+                        steps,
+                        first_seen,
+                        state,
+                    };
+                }
+            }
+            if !self.execute() {
+                return RunResult::Halted { steps, state };
+            }
+            steps += 1;
+        }
     }
 
     pub fn reset(&mut self, registers: [u64; 3]) {
         self.registers = registers;
         self.i = 0;
         self.output.clear();
+        if let Some(trace) = &mut self.trace {
+            trace.records.clear();
+        }
     }
 }
 
@@ -320,6 +563,8 @@ pub fn part2(input: &str) -> String {
     // i started out with all zeros and as I got better sim scores I added to the
     // the right side of this
     let input: [u64; 16] = [5, 0, 0, 0, 0, 3, 2, 7, 5, 6, 0, 2, 5, 0, 5, 2];
+    // anything printing more than the program itself can't be a quine
+    let limits = Limits::default().max_output(desired.len() + 1);
     let mut base = 0;
     let mut most_similar = 0;
     for j in &input {
@@ -329,7 +574,7 @@ pub fn part2(input: &str) -> String {
     for i in 0..u64::pow(8, 8) {
         let reg_a = base | (i << 33);
         computer.reset([reg_a, 0, 0]);
-        computer.execute_to_end();
+        computer.execute_to_end(&limits);
         let similarity_score: u64 = zip(computer.output.iter(), desired.iter())
             .map(|(a, b)| if a == b { 1 } else { 0 })
             .sum();
@@ -352,6 +597,35 @@ pub fn entrypoint(args: &Args) {
     if args.part2 {
         let res = part2(&input);
         println!("{}", res);
+    } else if args.max_steps.is_some() || args.trace.is_some() {
+        let mut computer = Computer::parse(&input);
+        if args.trace.is_some() {
+            computer.enable_trace();
+        }
+        let mut limits = Limits::default().detect_loops();
+        if let Some(max_steps) = args.max_steps {
+            limits = limits.max_steps(max_steps);
+        }
+        let res = computer.execute_to_end(&limits);
+        println!("{:?}", res);
+        println!("{}", computer.output.iter().join(","));
+        if let (Some(path), Some(trace)) = (&args.trace, &computer.trace) {
+            let contents = if path.ends_with(".json") {
+                trace.to_json()
+            } else {
+                trace.to_csv()
+            };
+            fs::write(path, contents).expect("I/O error");
+            let len = computer.program.len();
+            for (ip, hits) in trace.hit_counts(len).iter().enumerate().step_by(2) {
+                println!(
+                    "{ip:3}: {} {} x{hits}",
+                    mnemonic(computer.program[ip]),
+                    computer.program.get(ip + 1).unwrap_or(&0)
+                );
+            }
+            println!("never executed: {:?}", trace.never_executed(len));
+        }
     } else {
         let res = part1(&input);
         println!("{}", res);
@@ -380,23 +654,87 @@ Program: 0,1,5,4,3,0
 
     #[test]
     fn test_day17() {
-        assert_eq!(part1(TEST_GRID), "4635635210");
-        assert_eq!(part1(TEST_GRID_2), "42567777310");
+        assert_eq!(part1(TEST_GRID), "4,6,3,5,6,3,5,2,1,0");
+        assert_eq!(part1(TEST_GRID_2), "4,2,5,6,7,7,7,7,3,1,0");
 
         let mut computer = Computer::new(vec![2, 6], [0, 0, 9]);
-        computer.execute_to_end();
+        computer.execute_to_end(&Limits::default());
         assert_eq!(computer.registers[REG_B], 1);
 
         let mut computer = Computer::new(vec![5, 0, 5, 1, 5, 4], [10, 0, 0]);
-        computer.execute_to_end();
+        computer.execute_to_end(&Limits::default());
         assert_eq!(computer.output, vec![0, 1, 2]);
 
         let mut computer = Computer::new(vec![1, 7], [0, 29, 0]);
-        computer.execute_to_end();
+        computer.execute_to_end(&Limits::default());
         assert_eq!(computer.registers[REG_B], 26);
 
         let mut computer = Computer::new(vec![4, 0], [0, 2024, 43690]);
-        computer.execute_to_end();
+        computer.execute_to_end(&Limits::default());
         assert_eq!(computer.registers[REG_B], 44354);
     }
+
+    #[test]
+    fn test_day17_limits() {
+        let mut computer = Computer::parse(TEST_GRID);
+        let res = computer.execute_to_end(&Limits::default().max_steps(1000).detect_loops());
+        assert!(matches!(res, RunResult::Halted { .. }));
+        assert_eq!(computer.output, vec![4, 6, 3, 5, 6, 3, 5, 2, 1, 0]);
+
+        // bxl 0; jnz 0 never changes A so it spins in place
+        let mut computer = Computer::new(vec![1, 0, 3, 0], [1, 0, 0]);
+        let res = computer.execute_to_end(&Limits::default().detect_loops());
+        assert_eq!(
+            res,
+            RunResult::Looped {
+                steps: 2,
+                first_seen: 0,
+                state: State {
+                    i: 0,
+                    registers: [1, 0, 0]
+                }
+            }
+        );
+
+        let mut computer = Computer::new(vec![1, 0, 3, 0], [1, 0, 0]);
+        let res = computer.execute_to_end(&Limits::default().max_steps(7));
+        assert!(matches!(res, RunResult::StepLimit { steps: 7, .. }));
+
+        // out A; jnz 0 prints forever without A changing
+        let mut computer = Computer::new(vec![5, 4, 3, 0], [3, 0, 0]);
+        let res = computer.execute_to_end(&Limits::default().max_output(3));
+        assert!(matches!(res, RunResult::OutputLimit { steps: 5, .. }));
+        assert_eq!(computer.output, vec![3, 3, 3]);
+    }
+
+    #[test]
+    fn test_day17_trace() {
+        let mut computer = Computer::new(vec![2, 4, 5, 5, 3, 0], [10, 0, 0]);
+        computer.enable_trace();
+        computer.execute_to_end(&Limits::default().max_steps(3));
+        let trace = computer.trace.as_ref().unwrap();
+        assert_eq!(
+            trace.records[0],
+            TraceRecord {
+                ip: 0,
+                opcode: 2,
+                operand: 10,
+                before: [10, 0, 0],
+                after: [10, 2, 0],
+                output: None,
+            }
+        );
+        assert_eq!(trace.records[1].output, Some(2));
+        assert_eq!(trace.hit_counts(6), vec![1, 0, 1, 0, 1, 0]);
+        assert!(trace.to_csv().starts_with("ip,opcode,mnemonic"));
+        assert!(trace.to_csv().contains("\n2,5,out,2,10,2,0,10,2,0,2\n"));
+        assert!(trace.to_json().contains("\"mnemonic\":\"jnz\""));
+
+        // jnz 4 with A != 0 skips the out instruction entirely
+        let mut computer = Computer::new(vec![3, 4, 5, 4, 0, 1], [1, 0, 0]);
+        computer.enable_trace();
+        computer.execute_to_end(&Limits::default());
+        let trace = computer.trace.as_ref().unwrap();
+        assert_eq!(trace.never_executed(6), vec![2, 3]);
+    }
 }
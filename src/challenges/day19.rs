@@ -1,9 +1,6 @@
-use clap::{self, builder::Str};
-use min_max_heap::MinMaxHeap;
-use std::{
-    collections::{HashMap, VecDeque},
-    fs, str,
-};
+use advent24::trie::Trie;
+use clap;
+use std::{fs, str, thread};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -12,60 +9,70 @@ pub struct Args {
 
     #[clap(long, short, action)]
     part2: bool,
-}
 
-pub fn ways_to_make(word: String, alphabet: &[&str]) -> i64 {
-    let mut candidates = MinMaxHeap::new();
-    let mut idx_to_counts = HashMap::new();
-    let mut ways = 0;
-    candidates.push(0);
-    idx_to_counts.insert(0, 1);
-    while let Some(idx) = candidates.pop_min() {
-        for chunk in alphabet.iter() {
-            if idx + chunk.len() <= word.len() && word[idx..idx + chunk.len()] == **chunk {
-                let cur_idx_count: i64 = idx_to_counts[&idx];
-                if idx + chunk.len() >= word.len() {
-                    ways += cur_idx_count;
-                } else if !chunk.is_empty() {
-                    let new_idx = idx + chunk.len();
-                    if let Some(k) = idx_to_counts.get_mut(&new_idx) {
-                        *k += cur_idx_count;
-                    } else {
-                        candidates.push(new_idx);
-                        idx_to_counts.insert(new_idx, cur_idx_count);
-                    }
-                }
-            }
-        }
-    }
-    ways
+    /// Print up to this many arrangements of each design
+    #[arg(long)]
+    show: Option<usize>,
+
+    /// Print the arrangement of each design using the fewest towels
+    #[clap(long, action)]
+    shortest: bool,
+
+    /// Threads to count designs on (defaults to all available)
+    #[arg(long)]
+    threads: Option<usize>,
 }
 
-pub fn solver(input: &str) -> (i64, i64) {
+pub fn parse(input: &str) -> (Trie, Vec<&str>) {
     let mut parts = input.split("\n\n");
-    let alphabet: Vec<&str> = parts.next().unwrap().split(", ").collect();
-    let candidates: Vec<&str> = parts.next().unwrap().lines().collect();
-    let mut is_made_with_alphabet_count: i64 = 0;
-    let mut ways = 0;
-    for word in candidates {
-        let new_ways = ways_to_make(word.to_owned(), &alphabet);
-        if new_ways > 0 {
-            println!("✅ - {word} is made with alphabet");
-            is_made_with_alphabet_count += 1;
-            ways += new_ways;
-        } else {
-            println!("🚫 - {word} is not made with alphabet");
-        }
-    }
-    (is_made_with_alphabet_count, ways)
+    let alphabet: Vec<&str> = parts.next().unwrap().trim().split(", ").collect();
+    let designs: Vec<&str> = parts
+        .next()
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| !l.is_empty())
+        .collect();
+    (Trie::new(&alphabet), designs)
+}
+
+/// Number of possible designs, and the total number of ways to make them
+pub fn solver(input: &str, threads: usize) -> (u64, u64) {
+    let (trie, designs) = parse(input);
+    let ways = trie.count_all(&designs, threads);
+    let possible = ways.iter().filter(|w| **w > 0).count() as u64;
+    (possible, ways.iter().sum())
 }
 
 pub fn entrypoint(args: &Args) {
     let input = fs::read_to_string(&args.file).expect("I/O error");
-    let (p1, p2) = solver(&input);
-    println!("part1: {}", p1);
-    println!("part1: {}", p2);
-    println!("this day has not yet been implemented")
+    let threads = args
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    if args.show.is_some() || args.shortest {
+        let (trie, designs) = parse(&input);
+        for design in designs {
+            if !trie.can_segment(design) {
+                println!("🚫 - {design} is not made with alphabet");
+                continue;
+            }
+            println!("✅ - {design} is made with alphabet");
+            if let Some(limit) = args.show {
+                for pieces in trie.segmentations(design, limit) {
+                    println!("    {}", pieces.join(" "));
+                }
+            }
+            if args.shortest {
+                let pieces = trie.shortest_segmentation(design).unwrap();
+                println!("    shortest: {}", pieces.join(" "));
+            }
+        }
+    }
+    let (p1, p2) = solver(&input, threads);
+    if args.part2 {
+        println!("{}", p2);
+    } else {
+        println!("{}", p1);
+    }
 }
 
 #[cfg(test)]
@@ -86,11 +93,10 @@ bbrgwb";
 
     #[test]
     fn test() {
-        assert_eq!(
-            ways_to_make("derek".to_string(), &["de", "d", "e", "r", "k", "ek"]),
-            4
-        );
-        assert_eq!(ways_to_make("derek".to_string(), &["d", "e", "k"]), 0);
-        assert_eq!(solver(TEST_GRID), (6, 16));
+        let trie = Trie::new(&["de", "d", "e", "r", "k", "ek"]);
+        assert_eq!(trie.count_segmentations("derek"), 4);
+        assert_eq!(Trie::new(&["d", "e", "k"]).count_segmentations("derek"), 0);
+        assert_eq!(solver(TEST_GRID, 1), (6, 16));
+        assert_eq!(solver(TEST_GRID, 4), (6, 16));
     }
 }
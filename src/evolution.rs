@@ -0,0 +1,117 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// A count grew past `u128::MAX`, or the rule couldn't represent a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow;
+
+/// Evolves a multiset of values one generation at a time, where every value
+/// is replaced by the values `rule` returns for it. Only counts are tracked,
+/// so duplicates cost nothing no matter how many there are. The rule can fail
+/// with `Overflow` when a successor doesn't fit in `T`.
+pub struct Evolution<T, F> {
+    rule: F,
+    cache: HashMap<(T, usize), u128>,
+}
+
+pub fn to_counts<T: Hash + Eq + Clone>(values: &[T]) -> HashMap<T, u128> {
+    let mut counts: HashMap<T, u128> = HashMap::new();
+    for v in values {
+        *counts.entry(v.clone()).or_default() += 1;
+    }
+    counts
+}
+
+impl<T, F> Evolution<T, F>
+where
+    T: Hash + Eq + Clone,
+    F: Fn(&T) -> Result<Vec<T>, Overflow>,
+{
+    pub fn new(rule: F) -> Self {
+        Evolution {
+            rule,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn step(&self, counts: &HashMap<T, u128>) -> Result<HashMap<T, u128>, Overflow> {
+        let mut next: HashMap<T, u128> = HashMap::with_capacity(counts.len());
+        for (value, count) in counts {
+            for successor in (self.rule)(value)? {
+                let entry = next.entry(successor).or_default();
+                *entry = entry.checked_add(*count).ok_or(Overflow)?;
+            }
+        }
+        Ok(next)
+    }
+
+    pub fn run(
+        &self,
+        mut counts: HashMap<T, u128>,
+        generations: usize,
+    ) -> Result<HashMap<T, u128>, Overflow> {
+        for _ in 0..generations {
+            counts = self.step(&counts)?;
+        }
+        Ok(counts)
+    }
+
+    /// Total size of the multiset after `generations`
+    pub fn run_total(&self, values: &[T], generations: usize) -> Result<u128, Overflow> {
+        let counts = self.run(to_counts(values), generations)?;
+        counts
+            .values()
+            .try_fold(0u128, |total, c| total.checked_add(*c))
+            .ok_or(Overflow)
+    }
+
+    /// How many values `value` turns into after `depth` generations. Results are
+    /// cached per `(value, depth)` and reused across calls. This recurses
+    /// `depth` deep, so prefer `run_total` for very long runs.
+    pub fn count(&mut self, value: &T, depth: usize) -> Result<u128, Overflow> {
+        if depth == 0 {
+            return Ok(1);
+        }
+        if let Some(count) = self.cache.get(&(value.clone(), depth)) {
+            return Ok(*count);
+        }
+        let mut total: u128 = 0;
+        for successor in (self.rule)(value)? {
+            let count = self.count(&successor, depth - 1)?;
+            total = total.checked_add(count).ok_or(Overflow)?;
+        }
+        self.cache.insert((value.clone(), depth), total);
+        Ok(total)
+    }
+
+    /// Like `run_total`, but using the per-value cache
+    pub fn count_total(&mut self, values: &[T], generations: usize) -> Result<u128, Overflow> {
+        let mut total: u128 = 0;
+        for v in values {
+            let count = self.count(v, generations)?;
+            total = total.checked_add(count).ok_or(Overflow)?;
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evolution() {
+        // every value doubles, so the total is 2^generations per value
+        let mut doubling = Evolution::new(|v: &u8| Ok(vec![*v, *v]));
+        assert_eq!(doubling.run_total(&[1, 2, 1], 10), Ok(3 * 1024));
+        assert_eq!(doubling.count_total(&[1, 2, 1], 10), Ok(3 * 1024));
+        assert_eq!(doubling.run(to_counts(&[7]), 3).unwrap()[&7], 8);
+        assert_eq!(doubling.count(&7, 127), Ok(1 << 127));
+        assert_eq!(doubling.count(&7, 128), Err(Overflow));
+        assert_eq!(doubling.run_total(&[7], 128), Err(Overflow));
+
+        let mut growing = Evolution::new(|v: &u8| Ok(vec![v.checked_mul(2).ok_or(Overflow)?]));
+        assert_eq!(growing.run_total(&[1], 7), Ok(1));
+        assert_eq!(growing.run_total(&[1], 8), Err(Overflow));
+        assert_eq!(growing.count(&1, 8), Err(Overflow));
+    }
+}
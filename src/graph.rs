@@ -0,0 +1,599 @@
+use crate::{
+    bytegrid::{ByteGrid, Point},
+    dfs,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    hash::Hash,
+    ops::Add,
+};
+
+/// Every node in `graph`, including ones that only appear as successors
+pub fn nodes<T: Hash + Eq + Copy + Ord>(graph: &HashMap<T, Vec<T>>) -> Vec<T> {
+    let mut nodes: HashSet<T> = graph.keys().copied().collect();
+    nodes.extend(graph.values().flatten().copied());
+    let mut nodes: Vec<T> = nodes.into_iter().collect();
+    nodes.sort();
+    nodes
+}
+
+/// A cycle `[a, b, c]` (meaning `a -> b -> c -> a`), if there is one
+pub fn find_cycle<T: Hash + Eq + Copy + Ord>(graph: &HashMap<T, Vec<T>>) -> Option<Vec<T>> {
+    #[derive(PartialEq)]
+    enum Color {
+        InProgress,
+        Done,
+    }
+    let mut colors: HashMap<T, Color> = HashMap::new();
+    let no_successors: Vec<T> = vec![];
+    for root in nodes(graph) {
+        if colors.contains_key(&root) {
+            continue;
+        }
+        // the current path, with how far through each node's successors we are
+        let mut path: Vec<(T, usize)> = vec![(root, 0)];
+        colors.insert(root, Color::InProgress);
+        while let Some((node, next)) = path.last_mut() {
+            let successors = graph.get(node).unwrap_or(&no_successors);
+            if let Some(successor) = successors.get(*next) {
+                *next += 1;
+                match colors.get(successor) {
+                    Some(Color::InProgress) => {
+                        let start = path.iter().position(|(n, _)| n == successor).unwrap();
+                        return Some(path[start..].iter().map(|(n, _)| *n).collect());
+                    }
+                    Some(Color::Done) => (),
+                    None => {
+                        colors.insert(*successor, Color::InProgress);
+                        path.push((*successor, 0));
+                    }
+                }
+            } else {
+                colors.insert(*node, Color::Done);
+                path.pop();
+            }
+        }
+    }
+    None
+}
+
+/// Kahn's algorithm, where an edge `a -> b` means `a` comes before `b`. Ties
+/// are broken by picking the smallest node, so the result is deterministic.
+/// Returns a cycle if there is no valid order.
+pub fn toposort_kahn<T: Hash + Eq + Copy + Ord>(
+    graph: &HashMap<T, Vec<T>>,
+) -> Result<Vec<T>, Vec<T>> {
+    let nodes = nodes(graph);
+    let mut in_degree: HashMap<T, usize> = nodes.iter().map(|n| (*n, 0)).collect();
+    for successor in graph.values().flatten() {
+        *in_degree.get_mut(successor).unwrap() += 1;
+    }
+    let mut ready: BinaryHeap<Reverse<T>> = nodes
+        .iter()
+        .filter(|n| in_degree[n] == 0)
+        .map(|n| Reverse(*n))
+        .collect();
+    let mut order: Vec<T> = Vec::with_capacity(nodes.len());
+    while let Some(Reverse(node)) = ready.pop() {
+        order.push(node);
+        for successor in graph.get(&node).into_iter().flatten() {
+            let degree = in_degree.get_mut(successor).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                ready.push(Reverse(*successor));
+            }
+        }
+    }
+    if order.len() < nodes.len() {
+        return Err(find_cycle(graph).expect("unsorted nodes without a cycle"));
+    }
+    Ok(order)
+}
+
+/// Topological order from a reversed depth first postorder
+pub fn toposort_dfs<T: Hash + Eq + Copy + Ord>(
+    graph: &HashMap<T, Vec<T>>,
+) -> Result<Vec<T>, Vec<T>> {
+    if let Some(cycle) = find_cycle(graph) {
+        return Err(cycle);
+    }
+    let mut order = dfs(graph, &nodes(graph), true);
+    order.reverse();
+    Ok(order)
+}
+
+/// Every `(a, b)` where the graph says `a -> b` but `b` comes first in `seq`
+pub fn violations<T: Hash + Eq + Copy>(graph: &HashMap<T, Vec<T>>, seq: &[T]) -> Vec<(T, T)> {
+    let positions: HashMap<T, usize> = seq.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let mut found: Vec<(T, T)> = vec![];
+    for (i, node) in seq.iter().enumerate() {
+        for successor in graph.get(node).into_iter().flatten() {
+            if positions.get(successor).is_some_and(|j| *j < i) {
+                found.push((*node, *successor));
+            }
+        }
+    }
+    found
+}
+
+pub fn respects<T: Hash + Eq + Copy>(graph: &HashMap<T, Vec<T>>, seq: &[T]) -> bool {
+    violations(graph, seq).is_empty()
+}
+
+/// Only the edges between `nodes`, with every node present as a key
+pub fn induced_subgraph<T: Hash + Eq + Copy>(
+    graph: &HashMap<T, Vec<T>>,
+    nodes: &[T],
+) -> HashMap<T, Vec<T>> {
+    let keep: HashSet<T> = nodes.iter().copied().collect();
+    nodes
+        .iter()
+        .map(|n| {
+            let successors = graph
+                .get(n)
+                .into_iter()
+                .flatten()
+                .filter(|s| keep.contains(s))
+                .copied()
+                .collect();
+            (*n, successors)
+        })
+        .collect()
+}
+
+/// Sort `subset` so it respects every edge between its members
+pub fn reorder<T: Hash + Eq + Copy + Ord>(
+    graph: &HashMap<T, Vec<T>>,
+    subset: &[T],
+) -> Result<Vec<T>, Vec<T>> {
+    toposort_kahn(&induced_subgraph(graph, subset))
+}
+
+/// A directed graph stored as adjacency lists, with an `E` on every edge.
+/// Undirected graphs are just edges added in both directions.
+#[derive(Debug, Clone)]
+pub struct Graph<N, E = ()> {
+    adjacency: HashMap<N, Vec<(N, E)>>,
+}
+
+/// Distances from a start node, along with every predecessor that lies on
+/// some shortest path, so all tied paths can be recovered
+#[derive(Debug, Clone)]
+pub struct ShortestPaths<N, E> {
+    pub start: N,
+    pub cost: HashMap<N, E>,
+    pub predecessors: HashMap<N, Vec<N>>,
+}
+
+impl<N, E> Default for Graph<N, E> {
+    fn default() -> Self {
+        Graph {
+            adjacency: HashMap::new(),
+        }
+    }
+}
+
+impl<N, E> Graph<N, E>
+where
+    N: Hash + Eq + Copy + Ord,
+    E: Copy,
+{
+    pub fn new() -> Self {
+        Graph::default()
+    }
+
+    pub fn from_edges(edges: impl IntoIterator<Item = (N, N, E)>) -> Self {
+        let mut graph = Graph::new();
+        for (a, b, e) in edges {
+            graph.add_edge(a, b, e);
+        }
+        graph
+    }
+
+    pub fn from_undirected_edges(edges: impl IntoIterator<Item = (N, N, E)>) -> Self {
+        let mut graph = Graph::new();
+        for (a, b, e) in edges {
+            graph.add_undirected_edge(a, b, e);
+        }
+        graph
+    }
+
+    pub fn add_node(&mut self, node: N) {
+        self.adjacency.entry(node).or_default();
+    }
+
+    pub fn add_edge(&mut self, from: N, to: N, edge: E) {
+        self.add_node(to);
+        self.adjacency.entry(from).or_default().push((to, edge));
+    }
+
+    pub fn add_undirected_edge(&mut self, a: N, b: N, edge: E) {
+        self.add_edge(a, b, edge);
+        self.add_edge(b, a, edge);
+    }
+
+    /// Every node, sorted
+    pub fn nodes(&self) -> Vec<N> {
+        let mut nodes: Vec<N> = self.adjacency.keys().copied().collect();
+        nodes.sort();
+        nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.adjacency.is_empty()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.adjacency.values().map(Vec::len).sum()
+    }
+
+    pub fn successors(&self, node: N) -> &[(N, E)] {
+        self.adjacency.get(&node).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn has_edge(&self, from: N, to: N) -> bool {
+        self.successors(from).iter().any(|(n, _)| *n == to)
+    }
+
+    /// The same graph with every edge pointing the other way
+    pub fn transpose(&self) -> Self {
+        let mut graph = Graph::new();
+        for (from, successors) in self.adjacency.iter() {
+            graph.add_node(*from);
+            for (to, e) in successors {
+                graph.add_edge(*to, *from, *e);
+            }
+        }
+        graph
+    }
+
+    /// Plain successor lists, as used by `dfs` and the ordering functions
+    pub fn to_adjacency_map(&self) -> HashMap<N, Vec<N>> {
+        self.adjacency
+            .iter()
+            .map(|(n, successors)| (*n, successors.iter().map(|(s, _)| *s).collect()))
+            .collect()
+    }
+
+    /// Unweighted distance (in edges) to every node reachable from `start`
+    pub fn bfs(&self, start: N) -> HashMap<N, usize> {
+        let mut dist: HashMap<N, usize> = HashMap::from([(start, 0)]);
+        let mut queue: VecDeque<N> = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            let d = dist[&node];
+            for (successor, _) in self.successors(node) {
+                if !dist.contains_key(successor) {
+                    dist.insert(*successor, d + 1);
+                    queue.push_back(*successor);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Cheapest cost to every node reachable from `start`
+    pub fn dijkstra(&self, start: N) -> HashMap<N, E>
+    where
+        E: Ord + Add<Output = E> + Default,
+    {
+        self.shortest_paths(start).cost
+    }
+
+    /// Dijkstra, keeping every predecessor that ties for the cheapest cost.
+    /// Ties are only recorded for nodes that haven't been settled yet, so a
+    /// zero-weight cycle can't make a node its own predecessor.
+    pub fn shortest_paths(&self, start: N) -> ShortestPaths<N, E>
+    where
+        E: Ord + Add<Output = E> + Default,
+    {
+        let mut cost: HashMap<N, E> = HashMap::from([(start, E::default())]);
+        let mut predecessors: HashMap<N, Vec<N>> = HashMap::new();
+        let mut settled: HashSet<N> = HashSet::new();
+        let mut queue: BinaryHeap<Reverse<(E, N)>> =
+            BinaryHeap::from([Reverse((E::default(), start))]);
+        while let Some(Reverse((c, node))) = queue.pop() {
+            if !settled.insert(node) {
+                continue;
+            }
+            for (successor, e) in self.successors(node) {
+                let next = c + *e;
+                match cost.get(successor) {
+                    Some(best) if next > *best => (),
+                    _ if settled.contains(successor) => (),
+                    Some(best) if next == *best => {
+                        predecessors.entry(*successor).or_default().push(node);
+                    }
+                    _ => {
+                        cost.insert(*successor, next);
+                        predecessors.insert(*successor, vec![node]);
+                        queue.push(Reverse((next, *successor)));
+                    }
+                }
+            }
+        }
+        ShortestPaths {
+            start,
+            cost,
+            predecessors,
+        }
+    }
+
+    /// Weakly connected components, each sorted, in order of their smallest node
+    pub fn connected_components(&self) -> Vec<Vec<N>> {
+        let mut undirected: HashMap<N, Vec<N>> = self.to_adjacency_map();
+        for (from, successors) in self.adjacency.iter() {
+            for (to, _) in successors {
+                undirected.entry(*to).or_default().push(*from);
+            }
+        }
+        let mut seen: HashSet<N> = HashSet::new();
+        let mut components: Vec<Vec<N>> = vec![];
+        for node in self.nodes() {
+            if seen.contains(&node) {
+                continue;
+            }
+            let mut component = dfs(&undirected, &[node], false);
+            seen.extend(component.iter().copied());
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    /// Kosaraju's algorithm. Components are each sorted, and listed in
+    /// topological order of the condensed graph.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<N>> {
+        let mut order = dfs(&self.to_adjacency_map(), &self.nodes(), true);
+        order.reverse();
+        let transposed = self.transpose();
+        let mut assigned: HashSet<N> = HashSet::new();
+        let mut components: Vec<Vec<N>> = vec![];
+        for root in order {
+            if !assigned.insert(root) {
+                continue;
+            }
+            let mut component: Vec<N> = vec![];
+            let mut stack: Vec<N> = vec![root];
+            while let Some(node) = stack.pop() {
+                component.push(node);
+                for (predecessor, _) in transposed.successors(node) {
+                    if assigned.insert(*predecessor) {
+                        stack.push(*predecessor);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    /// The largest set of nodes that are all connected to each other (in
+    /// either direction), via Bron-Kerbosch with pivoting. Ties go to the
+    /// lexicographically smallest clique.
+    pub fn max_clique(&self) -> Vec<N> {
+        let mut neighbours: HashMap<N, HashSet<N>> = HashMap::new();
+        for (from, successors) in self.adjacency.iter() {
+            neighbours.entry(*from).or_default();
+            for (to, _) in successors.iter().filter(|(to, _)| to != from) {
+                neighbours.entry(*from).or_default().insert(*to);
+                neighbours.entry(*to).or_default().insert(*from);
+            }
+        }
+        let mut best: Vec<N> = vec![];
+        bron_kerbosch(
+            &neighbours,
+            &mut vec![],
+            self.nodes().into_iter().collect(),
+            HashSet::new(),
+            &mut best,
+        );
+        best
+    }
+}
+
+fn bron_kerbosch<N: Hash + Eq + Copy + Ord>(
+    neighbours: &HashMap<N, HashSet<N>>,
+    clique: &mut Vec<N>,
+    mut candidates: HashSet<N>,
+    mut excluded: HashSet<N>,
+    best: &mut Vec<N>,
+) {
+    if candidates.is_empty() && excluded.is_empty() {
+        let mut found = clique.clone();
+        found.sort();
+        if found.len() > best.len() || (found.len() == best.len() && found < *best) {
+            *best = found;
+        }
+        return;
+    }
+    // skip the pivot's neighbours, they're covered by branching on the pivot
+    let pivot = candidates
+        .union(&excluded)
+        .max_by_key(|n| neighbours[n].intersection(&candidates).count())
+        .copied()
+        .unwrap();
+    let mut branches: Vec<N> = candidates
+        .difference(&neighbours[&pivot])
+        .copied()
+        .collect();
+    branches.sort();
+    for node in branches {
+        let adjacent = &neighbours[&node];
+        clique.push(node);
+        bron_kerbosch(
+            neighbours,
+            clique,
+            candidates.intersection(adjacent).copied().collect(),
+            excluded.intersection(adjacent).copied().collect(),
+            best,
+        );
+        clique.pop();
+        candidates.remove(&node);
+        excluded.insert(node);
+    }
+}
+
+impl<N, E> ShortestPaths<N, E>
+where
+    N: Hash + Eq + Copy + Ord,
+{
+    /// Every node on any shortest path from the start to `target`
+    pub fn nodes_on_paths(&self, target: N) -> HashSet<N> {
+        let mut found: HashSet<N> = HashSet::new();
+        if !self.cost.contains_key(&target) {
+            return found;
+        }
+        let mut stack: Vec<N> = vec![target];
+        while let Some(node) = stack.pop() {
+            if found.insert(node) {
+                stack.extend(self.predecessors.get(&node).into_iter().flatten());
+            }
+        }
+        found
+    }
+
+    /// One shortest path from the start to `target`, preferring the smallest
+    /// predecessor wherever there's a tie
+    pub fn path(&self, target: N) -> Option<Vec<N>> {
+        self.cost.get(&target)?;
+        let mut path: Vec<N> = vec![target];
+        let mut node = target;
+        while node != self.start {
+            node = *self.predecessors[&node].iter().min()?;
+            path.push(node);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// How many distinct shortest paths lead to `target`
+    pub fn count_paths(&self, target: N) -> u64 {
+        fn count<N: Hash + Eq + Copy>(
+            node: N,
+            start: N,
+            predecessors: &HashMap<N, Vec<N>>,
+            memo: &mut HashMap<N, u64>,
+        ) -> u64 {
+            if node == start {
+                return 1;
+            }
+            if let Some(n) = memo.get(&node) {
+                return *n;
+            }
+            let total = predecessors
+                .get(&node)
+                .into_iter()
+                .flatten()
+                .map(|p| count(*p, start, predecessors, memo))
+                .sum();
+            memo.insert(node, total);
+            total
+        }
+        if !self.cost.contains_key(&target) {
+            return 0;
+        }
+        count(target, self.start, &self.predecessors, &mut HashMap::new())
+    }
+}
+
+impl Graph<Point, u32> {
+    /// Orthogonal moves of cost 1 between cells whose bytes are `passable`
+    pub fn from_grid(grid: &ByteGrid, passable: impl Fn(u8) -> bool) -> Self {
+        let mut graph = Graph::new();
+        for (idx, b) in grid.data.iter().enumerate() {
+            if !passable(*b) {
+                continue;
+            }
+            let point = grid.idx_to_point(idx);
+            graph.add_node(point);
+            for neighbour in point.orthogonals() {
+                if grid.get_point(neighbour).is_some_and(|n| passable(*n)) {
+                    graph.add_edge(point, neighbour, 1);
+                }
+            }
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toposort() {
+        let graph: HashMap<u8, Vec<u8>> =
+            HashMap::from([(5, vec![3, 1]), (3, vec![1]), (4, vec![2]), (2, vec![1])]);
+        assert_eq!(toposort_kahn(&graph), Ok(vec![4, 2, 5, 3, 1]));
+        let order = toposort_dfs(&graph).unwrap();
+        assert_eq!(order.len(), 5);
+        assert!(respects(&graph, &order));
+        assert_eq!(violations(&graph, &[1, 3, 5]), vec![(3, 1), (5, 3), (5, 1)]);
+        assert_eq!(reorder(&graph, &[1, 2, 3]), Ok(vec![2, 3, 1]));
+
+        let mut cyclic = graph.clone();
+        cyclic.insert(1, vec![4]);
+        assert_eq!(find_cycle(&graph), None);
+        assert_eq!(find_cycle(&cyclic), Some(vec![1, 4, 2]));
+        assert_eq!(toposort_kahn(&cyclic), Err(vec![1, 4, 2]));
+        assert!(toposort_dfs(&cyclic).is_err());
+        // the cycle doesn't matter if the subset avoids it
+        assert_eq!(reorder(&cyclic, &[5, 4, 3]), Ok(vec![4, 5, 3]));
+    }
+
+    #[test]
+    fn test_graph() {
+        let graph = Graph::from_edges([
+            ('a', 'b', 1),
+            ('a', 'c', 2),
+            ('b', 'c', 1),
+            ('b', 'd', 5),
+            ('c', 'd', 2),
+            ('d', 'b', 1),
+            ('e', 'f', 1),
+        ]);
+        assert_eq!(graph.len(), 6);
+        assert_eq!(graph.edge_count(), 7);
+        assert_eq!(graph.bfs('a')[&'d'], 2);
+        assert_eq!(graph.dijkstra('a')[&'d'], 4);
+        let paths = graph.shortest_paths('a');
+        assert_eq!(paths.path('d'), Some(vec!['a', 'c', 'd']));
+        assert_eq!(paths.count_paths('d'), 2);
+        assert_eq!(paths.nodes_on_paths('d').len(), 4);
+        assert_eq!(paths.path('e'), None);
+        assert_eq!(
+            graph.connected_components(),
+            vec![vec!['a', 'b', 'c', 'd'], vec!['e', 'f']]
+        );
+        assert_eq!(
+            graph.strongly_connected_components(),
+            vec![vec!['e'], vec!['f'], vec!['a'], vec!['b', 'c', 'd']]
+        );
+
+        let lan = Graph::from_undirected_edges(
+            [(1, 2), (1, 3), (2, 3), (3, 4), (2, 4), (1, 4), (4, 5)].map(|(a, b)| (a, b, ())),
+        );
+        assert_eq!(lan.max_clique(), vec![1, 2, 3, 4]);
+
+        let grid = ByteGrid::new("..#\n.##\n...");
+        let maze = Graph::from_grid(&grid, |b| b == b'.');
+        assert_eq!(maze.len(), 6);
+        assert_eq!(maze.dijkstra(Point::new(0, 0))[&Point::new(2, 2)], 4);
+        assert!(!maze.has_edge(Point::new(0, 1), Point::new(0, 2)));
+
+        // c and d are free to reach from each other, which mustn't turn into
+        // a loop of predecessors
+        let free_loop = Graph::from_edges([('a', 'c', 1), ('c', 'd', 0), ('d', 'c', 0)]);
+        let paths = free_loop.shortest_paths('a');
+        assert_eq!(paths.count_paths('c'), 1);
+        assert_eq!(paths.count_paths('d'), 1);
+        assert_eq!(paths.path('d'), Some(vec!['a', 'c', 'd']));
+        assert_eq!(paths.nodes_on_paths('d').len(), 3);
+    }
+}
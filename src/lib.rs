@@ -1,9 +1,14 @@
-#![feature(test, extract_if)]
+#![feature(test)]
 
+use bytegrid::Point;
 use std::collections::HashMap;
 use std::hash::Hash;
 
 pub mod bytegrid;
+pub mod evolution;
+pub mod graph;
+pub mod linalg;
+pub mod trie;
 
 pub fn parse_number_list<T: std::str::FromStr>(s: &str) -> Vec<T> {
     s.split_whitespace().flat_map(|x| x.parse()).collect()
@@ -89,6 +94,139 @@ pub fn dfs<T: Hash + Eq + Copy>(
     result
 }
 
+/// Where a sequence `x0, f(x0), f(f(x0)), ...` starts repeating: the state at
+/// step `start + period` is the same as the one at step `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// The earliest step with the same state as step `n`
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.period
+        }
+    }
+}
+
+/// Floyd's tortoise and hare. Only needs `PartialEq` and keeps two states in
+/// memory, but calls `step` about three times per step of the sequence.
+/// Gives up if no repeat is found within `limit` steps.
+pub fn find_cycle_floyd<T: PartialEq + Clone>(
+    initial: &T,
+    mut step: impl FnMut(&T) -> T,
+    limit: usize,
+) -> Option<Cycle> {
+    let mut tortoise = step(initial);
+    let mut hare = step(&tortoise);
+    let mut steps = 1;
+    while tortoise != hare {
+        if steps >= limit {
+            return None;
+        }
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        hare = step(&hare);
+        steps += 1;
+    }
+    let mut start = 0;
+    tortoise = initial.clone();
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+    let mut period = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        period += 1;
+    }
+    Some(Cycle { start, period })
+}
+
+/// Brent's algorithm: like Floyd's, but with fewer calls to `step`
+pub fn find_cycle_brent<T: PartialEq + Clone>(
+    initial: &T,
+    mut step: impl FnMut(&T) -> T,
+    limit: usize,
+) -> Option<Cycle> {
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(initial);
+    let mut steps = 1;
+    while tortoise != hare {
+        if steps >= limit {
+            return None;
+        }
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+        steps += 1;
+    }
+    tortoise = initial.clone();
+    hare = initial.clone();
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+    Some(Cycle { start, period })
+}
+
+/// Remembers every state, so `step` is only called once per step. Also
+/// returns the states seen (one per step, up to the first repeat, or all
+/// `limit + 1` of them if nothing repeats).
+pub fn find_cycle<T: Hash + Eq + Clone>(
+    initial: &T,
+    mut step: impl FnMut(&T) -> T,
+    limit: usize,
+) -> (Option<Cycle>, Vec<T>) {
+    let mut seen: HashMap<T, usize> = HashMap::new();
+    let mut state = initial.clone();
+    let mut cycle = None;
+    for i in 0..=limit {
+        if let Some(start) = seen.get(&state) {
+            cycle = Some(Cycle {
+                start: *start,
+                period: i - start,
+            });
+            break;
+        }
+        let next = if i < limit { Some(step(&state)) } else { None };
+        seen.insert(state, i);
+        match next {
+            Some(next) => state = next,
+            None => break,
+        }
+    }
+    // every state is only kept in `seen`, so put them back in order
+    let mut history: Vec<Option<T>> = vec![None; seen.len()];
+    for (state, i) in seen {
+        history[i] = Some(state);
+    }
+    (cycle, history.into_iter().flatten().collect())
+}
+
+/// The state after `n` steps, skipping ahead as soon as the sequence repeats
+pub fn nth_state<T: Hash + Eq + Clone>(initial: &T, step: impl FnMut(&T) -> T, n: usize) -> T {
+    let (cycle, mut history) = find_cycle(initial, step, n);
+    history.swap_remove(cycle.map_or(n, |c| c.reduce(n)))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Tile {
     pub x: usize,
@@ -116,6 +254,21 @@ impl Tile {
         }
     }
 
+    /// The tile at `point` in a `width` by `height` world, if it's inside it
+    pub fn from_point(point: Point, width: usize, height: usize) -> Option<Self> {
+        let x = usize::try_from(point.col).ok()?;
+        let y = usize::try_from(point.row).ok()?;
+        if x < width && y < height {
+            Some(Tile::new(width, height, x, y))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_point(&self) -> Point {
+        Point::new(self.y as i32, self.x as i32)
+    }
+
     pub fn char_at(&self, world: &[Vec<char>]) -> Option<char> {
         if self.y >= world.len() || self.x >= world[self.y].len() {
             None
@@ -195,3 +348,59 @@ impl Tile {
             .collect()
     }
 }
+
+impl From<Tile> for Point {
+    fn from(tile: Tile) -> Self {
+        tile.to_point()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles() {
+        // 2 -> 4 -> 16 -> 256 -> 471 -> 620 -> 16 -> ... (mod 1001)
+        let step = |x: &u64| x * x % 1001;
+        let expected = Cycle {
+            start: 2,
+            period: 4,
+        };
+        assert_eq!(find_cycle_floyd(&2, step, 100), Some(expected));
+        assert_eq!(find_cycle_brent(&2, step, 100), Some(expected));
+        let (cycle, history) = find_cycle(&2, step, 100);
+        assert_eq!(cycle, Some(expected));
+        assert_eq!(history, vec![2, 4, 16, 256, 471, 620]);
+        assert_eq!(nth_state(&2, step, 1_000_000_000_000), 471);
+        assert_eq!(nth_state(&2, step, 3), 256);
+
+        let count_up = |x: &u64| x + 1;
+        assert_eq!(find_cycle_floyd(&0, count_up, 100), None);
+        assert_eq!(find_cycle_brent(&0, count_up, 100), None);
+        let (cycle, history) = find_cycle(&0, count_up, 100);
+        assert_eq!(cycle, None);
+        assert_eq!(history, (0..=100).collect::<Vec<u64>>());
+        let mut calls = 0;
+        let counted = |x: &u64| {
+            calls += 1;
+            x + 1
+        };
+        assert_eq!(nth_state(&0, counted, 100), 100);
+        // no repeat, so every step is taken exactly once
+        assert_eq!(calls, 100);
+    }
+
+    #[test]
+    fn test_tile_point() {
+        let tile = Tile::new(5, 3, 4, 2);
+        assert_eq!(tile.to_point(), Point::new(2, 4));
+        assert_eq!(Tile::from_point(Point::from(tile), 5, 3), Some(tile));
+        assert_eq!(
+            tile.topleft().map(Point::from),
+            Some(Point::new(2, 4) + bytegrid::NORTHWEST)
+        );
+        assert_eq!(Tile::from_point(Point::new(3, 0), 5, 3), None);
+        assert_eq!(Tile::from_point(Point::new(0, -1), 5, 3), None);
+    }
+}
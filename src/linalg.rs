@@ -0,0 +1,386 @@
+use std::{
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+pub fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Returns `(g, x, y)` such that `a * x + b * y == g`, where `g` is the
+/// (non-negative) gcd of `a` and `b`
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        if a < 0 {
+            (-a, -1, 0)
+        } else {
+            (a, 1, 0)
+        }
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && ((a < 0) == (b < 0)) {
+        q + 1
+    } else {
+        q
+    }
+}
+
+/// Chinese remainder theorem: the `x` (and combined modulus) satisfying
+/// `x % m == r` for every `(r, m)`. Moduli don't need to be coprime, but
+/// `None` is returned if the congruences contradict each other.
+pub fn crt(congruences: &[(i128, i128)]) -> Option<(i128, i128)> {
+    let mut x: i128 = 0;
+    let mut modulus: i128 = 1;
+    for (r, m) in congruences.iter() {
+        let (g, p, _) = extended_gcd(modulus, *m);
+        let diff = r - x;
+        if diff % g != 0 {
+            return None;
+        }
+        let step = m / g;
+        let k = (diff / g * p).rem_euclid(step);
+        x += modulus * k;
+        modulus *= step;
+        x = x.rem_euclid(modulus);
+    }
+    Some((x, modulus))
+}
+
+/// An exact fraction, always stored in lowest terms with a positive denominator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i128,
+    den: i128,
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { num: 0, den: 1 };
+    pub const ONE: Rational = Rational { num: 1, den: 1 };
+
+    pub fn new(num: i128, den: i128) -> Self {
+        assert!(den != 0, "zero denominator");
+        let g = gcd(num, den);
+        let sign = if den < 0 { -1 } else { 1 };
+        Rational {
+            num: sign * num / g,
+            den: sign * den / g,
+        }
+    }
+
+    pub fn numer(&self) -> i128 {
+        self.num
+    }
+
+    pub fn denom(&self) -> i128 {
+        self.den
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    pub fn to_integer(&self) -> Option<i128> {
+        if self.den == 1 {
+            Some(self.num)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<i128> for Rational {
+    fn from(n: i128) -> Self {
+        Rational { num: n, den: 1 }
+    }
+}
+
+impl Add for Rational {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Rational::new(
+            self.num * other.den + other.num * self.den,
+            self.den * other.den,
+        )
+    }
+}
+
+impl Sub for Rational {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for Rational {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Rational::new(self.num * other.num, self.den * other.den)
+    }
+}
+
+impl Div for Rational {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        Rational::new(self.num * other.den, self.den * other.num)
+    }
+}
+
+impl Neg for Rational {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Rational {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Solution {
+    Unique(Vec<Rational>),
+    /// At least one unknown is free, so there are infinitely many solutions
+    Infinite,
+    Inconsistent,
+}
+
+/// Solve `a * x == b` exactly with Gauss-Jordan elimination. `a` is a list of
+/// rows and doesn't have to be square.
+pub fn solve(a: &[Vec<i128>], b: &[i128]) -> Solution {
+    let unknowns = a.first().map(|row| row.len()).unwrap_or(0);
+    let mut m: Vec<Vec<Rational>> = a
+        .iter()
+        .zip(b)
+        .map(|(row, rhs)| {
+            row.iter()
+                .chain([rhs])
+                .map(|v| Rational::from(*v))
+                .collect()
+        })
+        .collect();
+
+    let mut pivot_row = 0;
+    let mut pivot_cols: Vec<usize> = vec![];
+    for col in 0..unknowns {
+        let Some(found) = (pivot_row..m.len()).find(|r| !m[*r][col].is_zero()) else {
+            continue;
+        };
+        m.swap(pivot_row, found);
+        let pivot = m[pivot_row][col];
+        for v in m[pivot_row].iter_mut() {
+            *v = *v / pivot;
+        }
+        let pivot_vals = m[pivot_row].clone();
+        for (r, row) in m.iter_mut().enumerate() {
+            if r != pivot_row && !row[col].is_zero() {
+                let factor = row[col];
+                for (v, p) in row.iter_mut().zip(pivot_vals.iter()).skip(col) {
+                    *v = *v - *p * factor;
+                }
+            }
+        }
+        pivot_cols.push(col);
+        pivot_row += 1;
+    }
+
+    if m[pivot_row..].iter().any(|row| !row[unknowns].is_zero()) {
+        return Solution::Inconsistent;
+    }
+    if pivot_cols.len() < unknowns {
+        return Solution::Infinite;
+    }
+    Solution::Unique(m.iter().take(unknowns).map(|row| row[unknowns]).collect())
+}
+
+/// Solve `a * x == b`, only accepting a unique solution made of integers
+pub fn solve_integer(a: &[Vec<i128>], b: &[i128]) -> Option<Vec<i128>> {
+    match solve(a, b) {
+        Solution::Unique(x) => x.iter().map(Rational::to_integer).collect(),
+        _ => None,
+    }
+}
+
+/// Narrow `k_range` so that `start + k * step` stays within `[lo, hi]`
+fn constrain(
+    k_range: (Option<i128>, Option<i128>),
+    start: i128,
+    step: i128,
+    lo: i128,
+    hi: Option<i128>,
+) -> Option<(Option<i128>, Option<i128>)> {
+    let (mut k_lo, mut k_hi) = k_range;
+    let mut tighten = |bound: i128, is_lower: bool| {
+        // start + k * step >= bound (or <= bound)
+        let Some(diff) = bound.checked_sub(start) else {
+            return;
+        };
+        let at_least = is_lower == (step > 0);
+        if at_least {
+            let k = ceil_div(diff, step);
+            k_lo = Some(k_lo.map_or(k, |old| old.max(k)));
+        } else {
+            let k = floor_div(diff, step);
+            k_hi = Some(k_hi.map_or(k, |old| old.min(k)));
+        }
+    };
+    if step == 0 {
+        if start < lo || hi.is_some_and(|hi| start > hi) {
+            return None;
+        }
+    } else {
+        tighten(lo, true);
+        if let Some(hi) = hi {
+            tighten(hi, false);
+        }
+    }
+    match (k_lo, k_hi) {
+        (Some(k_lo), Some(k_hi)) if k_lo > k_hi => None,
+        range => Some(range),
+    }
+}
+
+/// Cheapest non-negative integer `x` (with every entry at most `max`, if
+/// given) such that `a * x == b`, where each unknown costs `cost[i]`.
+///
+/// Systems with a unique solution are supported for any number of unknowns.
+/// When the columns are dependent (e.g. two parallel button vectors) only two
+/// unknowns are supported: the single remaining equation is solved with the
+/// extended Euclidean algorithm and the cheapest point on it is chosen.
+pub fn min_cost_nonneg(
+    a: &[Vec<i128>],
+    b: &[i128],
+    cost: &[i128],
+    max: Option<i128>,
+) -> Option<Vec<i128>> {
+    let in_range = |v: &i128| *v >= 0 && max.is_none_or(|max| *v <= max);
+    match solve(a, b) {
+        Solution::Unique(x) => {
+            let x: Vec<i128> = x.iter().map(Rational::to_integer).collect::<Option<_>>()?;
+            x.iter().all(in_range).then_some(x)
+        }
+        Solution::Inconsistent => None,
+        Solution::Infinite if cost.len() == 2 => {
+            // rank is 1 (or 0), so any non-zero row describes the whole system
+            let Some((row, rhs)) = a.iter().zip(b).find(|(row, _)| row.iter().any(|v| *v != 0))
+            else {
+                return Some(vec![0, 0]);
+            };
+            let (p, q, r) = (row[0], row[1], *rhs);
+            let (g, s, t) = extended_gcd(p, q);
+            if r % g != 0 {
+                return None;
+            }
+            // every solution is (x0 + k * dx, y0 + k * dy)
+            let (x0, y0) = (s * (r / g), t * (r / g));
+            let (dx, dy) = (q / g, -p / g);
+            let range = constrain((None, None), x0, dx, 0, max)?;
+            let (k_lo, k_hi) = constrain(range, y0, dy, 0, max)?;
+            let slope = cost[0] * dx + cost[1] * dy;
+            let k = if slope > 0 {
+                k_lo?
+            } else if slope < 0 {
+                k_hi?
+            } else {
+                k_lo.or(k_hi).unwrap_or(0)
+            };
+            Some(vec![x0 + k * dx, y0 + k * dy])
+        }
+        Solution::Infinite => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rational() {
+        let half = Rational::new(2, 4);
+        assert_eq!(half, Rational::new(-1, -2));
+        assert_eq!(half + Rational::new(1, 3), Rational::new(5, 6));
+        assert_eq!((half * Rational::from(4)).to_integer(), Some(2));
+        assert_eq!(format!("{}", Rational::new(3, -6)), "-1/2");
+        assert_eq!(extended_gcd(240, 46), (2, -9, 47));
+        assert_eq!(extended_gcd(-4, 6).0, 2);
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        assert_eq!(crt(&[(1, 4), (3, 6)]), Some((9, 12)));
+        assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+    }
+
+    #[test]
+    fn test_solve() {
+        let a = vec![vec![2, 1, -1], vec![-3, -1, 2], vec![-2, 1, 2]];
+        assert_eq!(solve_integer(&a, &[8, -11, -3]), Some(vec![2, 3, -1]));
+        assert_eq!(
+            solve(&[vec![2, 0], vec![0, 3]], &[1, 1]),
+            Solution::Unique(vec![Rational::new(1, 2), Rational::new(1, 3)])
+        );
+        assert_eq!(
+            solve(&[vec![1, 1], vec![2, 2]], &[1, 3]),
+            Solution::Inconsistent
+        );
+        assert_eq!(
+            solve(&[vec![1, 1], vec![2, 2]], &[1, 2]),
+            Solution::Infinite
+        );
+
+        // day 13 example machine
+        let a = vec![vec![94, 22], vec![34, 67]];
+        assert_eq!(
+            min_cost_nonneg(&a, &[8400, 5400], &[3, 1], Some(100)),
+            Some(vec![80, 40])
+        );
+
+        // parallel buttons: B is cheaper per unit of distance...
+        let a = vec![vec![1, 3], vec![1, 3]];
+        assert_eq!(
+            min_cost_nonneg(&a, &[10, 10], &[3, 1], None),
+            Some(vec![1, 3])
+        );
+        // ...unless A covers more ground per token
+        let a = vec![vec![2, 1], vec![2, 1]];
+        assert_eq!(
+            min_cost_nonneg(&a, &[10, 10], &[1, 3], None),
+            Some(vec![5, 0])
+        );
+        assert_eq!(
+            min_cost_nonneg(&a, &[10, 10], &[1, 3], Some(4)),
+            Some(vec![4, 2])
+        );
+        let a = vec![vec![2, 4], vec![2, 4]];
+        assert_eq!(min_cost_nonneg(&a, &[5, 5], &[3, 1], None), None);
+    }
+}
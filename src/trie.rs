@@ -0,0 +1,212 @@
+use std::{collections::HashMap, thread};
+
+#[derive(Debug, Default, Clone)]
+struct TrieNode {
+    children: HashMap<u8, usize>,
+    terminal: bool,
+}
+
+/// A set of patterns for splitting text into pieces, where every piece has to
+/// be one of the patterns. Matching walks the trie once per start position
+/// instead of comparing against every pattern in turn.
+#[derive(Debug, Clone)]
+pub struct Trie {
+    nodes: Vec<TrieNode>,
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Trie {
+            nodes: vec![TrieNode::default()],
+        }
+    }
+}
+
+impl Trie {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Self {
+        let mut trie = Trie::default();
+        for pattern in patterns {
+            trie.insert(pattern.as_ref());
+        }
+        trie
+    }
+
+    /// Empty patterns are ignored, since they would allow endless segmentations
+    pub fn insert(&mut self, pattern: &str) {
+        if pattern.is_empty() {
+            return;
+        }
+        let mut node = 0;
+        for b in pattern.bytes() {
+            node = match self.nodes[node].children.get(&b) {
+                Some(next) => *next,
+                None => {
+                    self.nodes.push(TrieNode::default());
+                    let next = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(b, next);
+                    next
+                }
+            };
+        }
+        self.nodes[node].terminal = true;
+    }
+
+    /// Lengths of every pattern that matches `text` at `start`, shortest first
+    pub fn matches_at(&self, text: &[u8], start: usize) -> Vec<usize> {
+        let mut lengths: Vec<usize> = vec![];
+        let mut node = 0;
+        for (i, b) in text[start..].iter().enumerate() {
+            match self.nodes[node].children.get(b) {
+                Some(next) => node = *next,
+                None => break,
+            }
+            if self.nodes[node].terminal {
+                lengths.push(i + 1);
+            }
+        }
+        lengths
+    }
+
+    /// For every position, the number of ways to segment the rest of `text`
+    fn suffix_counts(&self, text: &[u8]) -> Vec<u64> {
+        let mut ways: Vec<u64> = vec![0; text.len() + 1];
+        ways[text.len()] = 1;
+        for start in (0..text.len()).rev() {
+            ways[start] = self
+                .matches_at(text, start)
+                .iter()
+                .map(|len| ways[start + len])
+                .sum();
+        }
+        ways
+    }
+
+    pub fn count_segmentations(&self, text: &str) -> u64 {
+        self.suffix_counts(text.as_bytes())[0]
+    }
+
+    pub fn can_segment(&self, text: &str) -> bool {
+        let text = text.as_bytes();
+        let mut reachable: Vec<bool> = vec![false; text.len() + 1];
+        reachable[0] = true;
+        for start in 0..text.len() {
+            if reachable[start] {
+                for len in self.matches_at(text, start) {
+                    reachable[start + len] = true;
+                }
+            }
+        }
+        reachable[text.len()]
+    }
+
+    /// Up to `limit` segmentations, in order of their piece lengths (shorter
+    /// pieces first)
+    pub fn segmentations<'a>(&self, text: &'a str, limit: usize) -> Vec<Vec<&'a str>> {
+        let ways = self.suffix_counts(text.as_bytes());
+        let mut found: Vec<Vec<&'a str>> = vec![];
+        let mut pieces: Vec<&'a str> = vec![];
+        self.enumerate(text, 0, &ways, limit, &mut pieces, &mut found);
+        found
+    }
+
+    fn enumerate<'a>(
+        &self,
+        text: &'a str,
+        start: usize,
+        ways: &[u64],
+        limit: usize,
+        pieces: &mut Vec<&'a str>,
+        found: &mut Vec<Vec<&'a str>>,
+    ) {
+        if found.len() >= limit {
+            return;
+        }
+        if start == text.len() {
+            found.push(pieces.clone());
+            return;
+        }
+        for len in self.matches_at(text.as_bytes(), start) {
+            // don't wander down prefixes that can't be finished
+            if ways[start + len] == 0 {
+                continue;
+            }
+            pieces.push(&text[start..start + len]);
+            self.enumerate(text, start + len, ways, limit, pieces, found);
+            pieces.pop();
+        }
+    }
+
+    /// A segmentation using the fewest pieces
+    pub fn shortest_segmentation<'a>(&self, text: &'a str) -> Option<Vec<&'a str>> {
+        let bytes = text.as_bytes();
+        // (pieces needed to finish from here, length of the first piece)
+        let mut best: Vec<Option<(usize, usize)>> = vec![None; bytes.len() + 1];
+        best[bytes.len()] = Some((0, 0));
+        for start in (0..bytes.len()).rev() {
+            best[start] = self
+                .matches_at(bytes, start)
+                .into_iter()
+                .filter_map(|len| best[start + len].map(|(pieces, _)| (pieces + 1, len)))
+                .min_by_key(|(pieces, _)| *pieces);
+        }
+        best[0]?;
+        let mut pieces: Vec<&'a str> = vec![];
+        let mut start = 0;
+        while let Some((_, len)) = best[start].filter(|_| start < bytes.len()) {
+            pieces.push(&text[start..start + len]);
+            start += len;
+        }
+        Some(pieces)
+    }
+
+    /// `count_segmentations` for every text, split across `threads` threads
+    pub fn count_all<S: AsRef<str> + Sync>(&self, texts: &[S], threads: usize) -> Vec<u64> {
+        let chunk_size = texts.len().div_ceil(threads.max(1)).max(1);
+        thread::scope(|scope| {
+            let handles: Vec<_> = texts
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|text| self.count_segmentations(text.as_ref()))
+                            .collect::<Vec<u64>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trie() {
+        let trie = Trie::new(&["de", "d", "e", "r", "k", "ek", ""]);
+        assert_eq!(trie.matches_at(b"derek", 0), vec![1, 2]);
+        assert_eq!(trie.count_segmentations("derek"), 4);
+        assert!(trie.can_segment("derek"));
+        assert!(!trie.can_segment("dex"));
+        assert_eq!(trie.count_segmentations(""), 1);
+        assert_eq!(
+            trie.segmentations("derek", 2),
+            vec![vec!["d", "e", "r", "e", "k"], vec!["d", "e", "r", "ek"]]
+        );
+        assert_eq!(trie.segmentations("derek", 10).len(), 4);
+        assert_eq!(
+            trie.shortest_segmentation("derek"),
+            Some(vec!["de", "r", "ek"])
+        );
+        assert_eq!(trie.shortest_segmentation("dex"), None);
+        assert_eq!(
+            trie.count_all(&["derek", "dex", "dee", "k"], 3),
+            vec![4, 0, 2, 1]
+        );
+    }
+}